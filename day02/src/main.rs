@@ -1,3 +1,6 @@
+use std::fs;
+use std::env;
+use std::fmt::Write;
 use std::io::{self, Read};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -7,62 +10,220 @@ fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let (p1, p2) = solve(&input);
+	let mut args = env::args().skip(1).collect::<Vec<_>>();
+	let csv      = args.iter().position(|a| a == "csv").map(|i| args.remove(i)).is_some();
+	let json     = args.iter().position(|a| a == "json").map(|i| args.remove(i)).is_some();
+	let limits   = with_limits(LIMITS, &args)?;
+
+	let (colors, games) = parse(&input);
+	let reports = report(&games, &bag_limits(&colors, &limits));
+
+	if csv {
+		fs::write("report.csv", generate_csv(&colors, &reports))?;
+		println!("Saved a per-game report to report.csv");
+	}
+	if json {
+		fs::write("report.json", generate_json(&colors, &reports))?;
+		println!("Saved a per-game report to report.json");
+	}
+
+	let (p1, p2) = totals(&reports);
 	println!("p1 = {p1}");
 	println!("p2 = {p2}");
 
 	Ok(())
 }
 
-fn solve(input: &str) -> (i32, i32) {
-	const LIMITS: [i32; 3] = [12, 13, 14];
-	input
+/// Cubes in the bag, colours not listed here are not in the bag at all.
+const LIMITS: &[(&str, i32)] = &[("red", 12), ("green", 13), ("blue", 14)];
+
+struct Game {
+	id:     i32,
+	rounds: Vec<Vec<(usize, i32)>>,
+}
+
+struct GameReport {
+	id:        i32,
+	worst:     Vec<i32>,
+	/// First `(round, color)` which took more cubes than the bag has.
+	violation: Option<(usize, usize)>,
+	power:     i32,
+	legit:     bool,
+}
+
+fn report(games: &[Game], limits: &[i32]) -> Vec<GameReport> {
+	games
+		.iter()
+		.map(|g| {
+			let worst     = worst(g, limits.len());
+			let violation = g
+				.rounds
+				.iter()
+				.enumerate()
+				.find_map(|(r, cubes)| cubes
+						  .iter()
+						  .find(|&&(c, k)| k > limits[c])
+						  .map(|&(c, _)| (r, c))
+				);
+			let power = worst.iter().product();
+			GameReport { id: g.id, worst, violation, power, legit: violation.is_none() }
+		})
+		.collect()
+}
+
+fn totals(reports: &[GameReport]) -> (i32, i32) {
+	reports
+		.iter()
+		.fold((0, 0), |acc, r| (if r.legit { acc.0 + r.id } else { acc.0 }, acc.1 + r.power))
+}
+
+fn parse(input: &str) -> (Vec<&str>, Vec<Game>) {
+	let mut colors = Vec::new();
+	let games = input
 		.lines()
 		.filter_map(|s| {
-			let s         = s.strip_prefix("Game ")?;
-			let (id, s)   = s.split_once(": ")?;
-			let id        = id.parse::<i32>().ok()?;
-			let worst     = s
+			let s       = s.strip_prefix("Game ")?;
+			let (id, s) = s.split_once(": ")?;
+			let id      = id.parse::<i32>().ok()?;
+			let rounds  = s
 				.split(';')
 				.map(|r| r
 						.split(',')
 						.filter_map(|s| {
-							let (k, c) = s.trim_start().split_once(' ')?;
+							let (k, c) = s.trim().split_once(' ')?;
 							let k = k.parse::<i32>().ok()?;
-							let c = parse_color(c);
-							Some((k, c))
-						})
-						.fold([0, 0, 0], |mut acc, (k, c)| {
-							acc[c] = acc[c].max(k);
-							acc
+							let c = intern(&mut colors, c.trim());
+							Some((c, k))
 						})
+						.collect()
 				)
-				.fold([0, 0, 0], |mut acc, cubes| {
-					acc[0] = acc[0].max(cubes[0]);
-					acc[1] = acc[1].max(cubes[1]);
-					acc[2] = acc[2].max(cubes[2]);
-					acc
-				});
-			let legit = worst[0] <= LIMITS[0] && worst[1] <= LIMITS[1] && worst[2] <= LIMITS[2];
-			let power = worst[0] * worst[1] * worst[2];
-			Some((id, legit, power))
+				.collect();
+			Some(Game { id, rounds })
 		})
-		.fold((0, 0), |acc, (id, legit, power)| (if legit { acc.0 + id } else { acc.0 }, acc.1 + power))
+		.collect();
+	(colors, games)
 }
 
-fn parse_color(s: &str) -> usize {
-	match s {
-		"red"   => 0,
-		"green" => 1,
-		"blue"  => 2,
-		_ => panic!("Unexpected color"),
+fn intern<'a>(colors: &mut Vec<&'a str>, name: &'a str) -> usize {
+	colors.iter().position(|&c| c == name).unwrap_or_else(|| {
+		colors.push(name);
+		colors.len() - 1
+	})
+}
+
+/// Overrides or adds limits from `<color>=<count>` arguments.
+fn with_limits<'a>(limits: &[(&'a str, i32)], args: &'a [String]) -> Result<Vec<(&'a str, i32)>> {
+	let mut limits = limits.to_vec();
+	for arg in args {
+		let (color, k) = arg.split_once('=').ok_or_else(|| format!("Expected csv, json or <color>=<count>, got '{arg}'"))?;
+		let k = k.parse().map_err(|_| format!("Count '{k}' of {color} is not a number"))?;
+		match limits.iter_mut().find(|(name, _)| *name == color) {
+			Some(limit) => limit.1 = k,
+			None        => limits.push((color, k)),
+		}
 	}
+	Ok(limits)
+}
+
+fn bag_limits(colors: &[&str], limits: &[(&str, i32)]) -> Vec<i32> {
+	colors
+		.iter()
+		.map(|c| limits
+			 .iter()
+			 .find(|(name, _)| name == c)
+			 .map_or(0, |&(_, k)| k)
+		)
+		.collect()
+}
+
+fn worst(game: &Game, n: usize) -> Vec<i32> {
+	game
+		.rounds
+		.iter()
+		.flatten()
+		.fold(vec![0; n], |mut acc, &(c, k)| {
+			acc[c] = acc[c].max(k);
+			acc
+		})
+}
+
+fn generate_csv(colors: &[&str], reports: &[GameReport]) -> String {
+	let mut buf = String::with_capacity(reports.len() * 32);
+
+	_ = write!(&mut buf, "game");
+	for c in colors {
+		_ = write!(&mut buf, ",max_{c}");
+	}
+	_ = writeln!(&mut buf, ",violation_round,violation_color,power,part1");
+
+	for r in reports {
+		_ = write!(&mut buf, "{}", r.id);
+		for k in &r.worst {
+			_ = write!(&mut buf, ",{k}");
+		}
+		match r.violation {
+			Some((round, c)) => _ = write!(&mut buf, ",{},{}", round + 1, colors[c]),
+			None             => _ = write!(&mut buf, ",,"),
+		}
+		_ = writeln!(&mut buf, ",{},{}", r.power, r.legit);
+	}
+
+	buf
+}
+
+fn generate_json(colors: &[&str], reports: &[GameReport]) -> String {
+	let mut buf = String::with_capacity(reports.len() * 128);
+
+	_ = writeln!(&mut buf, "[");
+	for (i, r) in reports.iter().enumerate() {
+		_ = write!(&mut buf, "  {{\"game\": {}, \"max\": {{", r.id);
+		for (j, (c, k)) in colors.iter().zip(&r.worst).enumerate() {
+			if j != 0 {
+				_ = write!(&mut buf, ", ");
+			}
+			_ = write!(&mut buf, "{}: {k}", json_string(c));
+		}
+		_ = write!(&mut buf, "}}, \"violation\": ");
+		match r.violation {
+			Some((round, c)) => _ = write!(&mut buf, "{{\"round\": {}, \"color\": {}}}", round + 1, json_string(colors[c])),
+			None             => _ = write!(&mut buf, "null"),
+		}
+		_ = write!(&mut buf, ", \"power\": {}, \"part1\": {}}}", r.power, r.legit);
+		_ = writeln!(&mut buf, "{}", if i + 1 == reports.len() { "" } else { "," });
+	}
+	_ = writeln!(&mut buf, "]");
+
+	buf
+}
+
+/// Quotes a string for JSON, whose escapes differ from what `{:?}` gives.
+fn json_string(s: &str) -> String {
+	let mut buf = String::with_capacity(s.len() + 2);
+	buf.push('"');
+	for c in s.chars() {
+		match c {
+			'"'          => buf.push_str("\\\""),
+			'\\'         => buf.push_str("\\\\"),
+			'\n'         => buf.push_str("\\n"),
+			'\r'         => buf.push_str("\\r"),
+			'\t'         => buf.push_str("\\t"),
+			c if c < ' ' => _ = write!(&mut buf, "\\u{:04x}", c as u32),
+			c            => buf.push(c),
+		}
+	}
+	buf.push('"');
+	buf
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
 
+	fn solve(input: &str, limits: &[(&str, i32)]) -> (i32, i32) {
+		let (colors, games) = parse(input);
+		totals(&report(&games, &bag_limits(&colors, limits)))
+	}
+
 	const INPUT: &str = r#"Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
 Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
 Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
@@ -71,11 +232,48 @@ Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green"#;
 
 	#[test]
 	fn test_part1() {
-		assert_eq!(solve(INPUT).0, 8);
+		assert_eq!(solve(INPUT, LIMITS).0, 8);
 	}
 
 	#[test]
 	fn test_part2() {
-		assert_eq!(solve(INPUT).1, 2286);
+		assert_eq!(solve(INPUT, LIMITS).1, 2286);
+	}
+
+	#[test]
+	fn test_extra_colors() {
+		const INPUT: &str = r#"Game 1: 3 blue, 4 red, 1 yellow; 2 green
+Game 2: 1 blue, 2 green, 4 red; 2 yellow, 1 blue
+Game 3: 1 blue, 2 green, 3 red, 1 yellow"#;
+		let (colors, _) = parse(INPUT);
+		assert_eq!(colors, ["blue", "red", "yellow", "green"]);
+		assert_eq!(solve(INPUT, LIMITS), (0, 24 + 16 + 6));
+		assert_eq!(solve(INPUT, &[("yellow", 1), ("red", 12), ("green", 13), ("blue", 14)]).0, 1 + 3);
+
+		let args = ["yellow=1".to_string()];
+		assert_eq!(solve(INPUT, &with_limits(LIMITS, &args).unwrap()).0, 1 + 3);
+		let args = ["yellow=2".to_string(), "red=3".to_string()];
+		assert_eq!(solve(INPUT, &with_limits(LIMITS, &args).unwrap()).0, 3);
+		assert!(with_limits(LIMITS, &["yellow=many".to_string()]).is_err());
+	}
+
+	#[test]
+	fn test_report() {
+		let (colors, games) = parse(INPUT);
+		let reports = report(&games, &bag_limits(&colors, LIMITS));
+		let csv = generate_csv(&colors, &reports);
+		let mut lines = csv.lines();
+		assert_eq!(lines.next(), Some("game,max_blue,max_red,max_green,violation_round,violation_color,power,part1"));
+		assert_eq!(lines.next(), Some("1,6,4,2,,,48,true"));
+		assert_eq!(lines.nth(1), Some("3,6,20,13,1,red,1560,false"));
+		assert_eq!(lines.next(), Some("4,15,14,3,3,blue,630,false"));
+
+		let json = generate_json(&colors, &reports[2..3]);
+		assert_eq!(json, "[\n  {\"game\": 3, \"max\": {\"blue\": 6, \"red\": 20, \"green\": 13}, \"violation\": {\"round\": 1, \"color\": \"red\"}, \"power\": 1560, \"part1\": false}\n]\n");
+
+		// Colours come from the input, so they are escaped the JSON way.
+		let (colors, games) = parse("Game 1: 1 a\"b\x1b\\");
+		let reports = report(&games, &bag_limits(&colors, LIMITS));
+		assert!(generate_json(&colors, &reports).contains(r#"{"a\"b\u001b\\": 1}"#));
 	}
 }
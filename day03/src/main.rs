@@ -1,6 +1,8 @@
-#![feature(byte_slice_trim_ascii)]
-#![feature(vec_push_within_capacity)]
-
+use std::fs;
+use std::env;
+use std::fmt::Write;
+use std::ops::Range;
+use std::str::FromStr;
 use std::collections::HashMap;
 use std::io::{self, Read};
 
@@ -11,98 +13,330 @@ fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let (p1, p2) = solve(&input);
+	let mut rule   = GEAR;
+	let mut export = None;
+	for arg in env::args().skip(1) {
+		match arg.as_str() {
+			"dot" | "json" => export = Some(arg),
+			_              => rule = arg.parse()?,
+		}
+	}
+
+	if let Some(export) = export {
+		let graph = graph(&input);
+		let stats = graph.stats();
+		let (file, data) = if export == "dot" {
+			("graph.dot", generate_dot(&graph))
+		} else {
+			("graph.json", generate_json(&graph, &stats))
+		};
+		fs::write(file, data)?;
+		println!("Saved a part/symbol graph to {file}");
+		println!("symbols without parts     = {}", stats.lonely_symbols);
+		println!("parts with many symbols   = {}", stats.shared_parts);
+		println!("largest cluster (p, s)    = {:?}", stats.largest_cluster);
+	}
+
+	let (p1, p2) = solve(&input, &rule);
 	println!("p1 = {p1}");
 	println!("p2 = {p2}");
 
 	Ok(())
 }
 
-fn solve(input: &str) -> (i32, i32) {
-	const DIR: [(i8, i8); 8] = [
-		(-1, -1), // nw
-		(-1,  0), // n
-		(-1,  1), // ne
-		( 0, -1), // w
-		( 0,  1), // e
-		( 1, -1), // sw
-		( 1,  0), // s
-		( 1,  1), // se
-	];
+#[derive(Debug, PartialEq)]
+struct Part {
+	num:     i32,
+	row:     usize,
+	cols:    Range<usize>,
+	/// Adjacent symbols as `(row, col, symbol)`.
+	symbols: Vec<(usize, usize, u8)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Count {
+	Exactly(usize),
+	AtLeast(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combine {
+	Product,
+	Sum,
+}
+
+/// Which symbol makes a gear, how many parts it needs and how to get its ratio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GearRule {
+	symbol:  u8,
+	count:   Count,
+	combine: Combine,
+}
+
+const GEAR: GearRule = GearRule { symbol: b'*', count: Count::Exactly(2), combine: Combine::Product };
 
-	let w = input.find('\n').unwrap_or(input.len()) as i32;
-	let h = input.trim_end().bytes().filter(|&x| x == b'\n').count() as i32 + 1;
+/// Parses rules like `*:2:product` or `#:3+:sum`.
+impl FromStr for GearRule {
+	type Err = String;
 
-	let mut m = Vec::with_capacity((w * h) as usize);
-	for x in input.bytes().filter(|&x| x != b'\n') {
-		_ = m.push_within_capacity(x);
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let err = || format!("Bad gear rule '{s}', expected <symbol>:<n>[+]:<product|sum>");
+
+		let mut it = s.split(':');
+		let (Some(symbol), Some(count), Some(combine), None) = (it.next(), it.next(), it.next(), it.next()) else {
+			return Err(err());
+		};
+
+		let &[symbol] = symbol.as_bytes() else { return Err(err()) };
+		let count = match count.strip_suffix('+') {
+			Some(n) => Count::AtLeast(n.parse().map_err(|_| err())?),
+			None    => Count::Exactly(count.parse().map_err(|_| err())?),
+		};
+		let combine = match combine {
+			"product" => Combine::Product,
+			"sum"     => Combine::Sum,
+			_         => return Err(err()),
+		};
+
+		Ok(GearRule { symbol, count, combine })
 	}
+}
+
+fn solve(input: &str, rule: &GearRule) -> (i32, i32) {
+	let parts = parts(input);
+
+	let sum = parts
+		.iter()
+		.filter(|p| !p.symbols.is_empty())
+		.map(|p| p.num)
+		.sum();
 
-	let mut sum   = 0;
+	let gears = gears(&parts, rule);
+
+	(sum, gears)
+}
+
+fn gears(parts: &[Part], rule: &GearRule) -> i32 {
 	let mut gears = HashMap::new();
+	for p in parts {
+		for &(r, c, x) in &p.symbols {
+			if x == rule.symbol {
+				gears.entry((r, c)).or_insert_with(Vec::new).push(p.num);
+			}
+		}
+	}
 
-	for r in 0..h {
-		let mut c = 0;
-		while c < h {
-			let mut num  = 0;
-			let mut good = false;
-			let mut star = None;
-
-			loop {
-				let x = m[(r * w) as usize + c as usize];
-				if !x.is_ascii_digit() {
-					c += 1;
-					break;
-				}
+	gears
+		.into_values()
+		.filter(|ns| match rule.count {
+			Count::Exactly(n) => ns.len() == n,
+			Count::AtLeast(n) => ns.len() >= n,
+		})
+		.map(|ns| match rule.combine {
+			Combine::Product => ns.iter().product::<i32>(),
+			Combine::Sum     => ns.iter().sum(),
+		})
+		.sum()
+}
 
-				num = num * 10 + (x - b'0') as i32;
+fn is_symbol(b: u8) -> bool {
+	b != b'.' && !b.is_ascii_digit()
+}
 
-				fn is_symbol(b: u8) -> bool {
-					b != b'.' && !b.is_ascii_digit()
-				}
+/// Bipartite graph between part numbers and every symbol of the schematic.
+struct Graph {
+	parts:   Vec<Part>,
+	/// Symbols as `(row, col, symbol)`.
+	symbols: Vec<(usize, usize, u8)>,
+	/// Edges as `(part, symbol)` indices.
+	edges:   Vec<(usize, usize)>,
+}
 
-				for (dr, dc) in DIR {
-					let r = r + dr as i32;
-					let c = c + dc as i32;
-					if r < 0 || r == h || c < 0 || c > w {
-						continue;
-					}
-					let x = m[(r * w) as usize + c as usize];
-					if !good {
-						good = is_symbol(x);
-						if x == b'*' {
-							star = Some((r, c));
-						}
-					}
-				}
+#[derive(Debug, PartialEq)]
+struct Stats {
+	lonely_symbols:  usize,
+	shared_parts:    usize,
+	/// Number of parts and symbols in the largest connected component.
+	largest_cluster: (usize, usize),
+}
+
+fn graph(input: &str) -> Graph {
+	let parts = parts(input);
+
+	let symbols = input
+		.lines()
+		.enumerate()
+		.flat_map(|(r, s)| s
+				  .trim_end()
+				  .bytes()
+				  .enumerate()
+				  .filter(|&(_, x)| is_symbol(x))
+				  .map(move |(c, x)| (r, c, x))
+		)
+		.collect::<Vec<_>>();
+
+	let ids = &symbols
+		.iter()
+		.enumerate()
+		.map(|(i, &(r, c, _))| ((r, c), i))
+		.collect::<HashMap<_, _>>();
+
+	let edges = parts
+		.iter()
+		.enumerate()
+		.flat_map(|(i, p)| p.symbols.iter().filter_map(move |&(r, c, _)| Some((i, *ids.get(&(r, c))?))))
+		.collect();
+
+	Graph { parts, symbols, edges }
+}
+
+impl Graph {
+	fn stats(&self) -> Stats {
+		let mut degree = vec![0; self.symbols.len()];
+		for &(_, s) in &self.edges {
+			degree[s] += 1;
+		}
+
+		let lonely_symbols = degree.iter().filter(|&&d| d == 0).count();
+		let shared_parts   = self.parts.iter().filter(|p| p.symbols.len() > 1).count();
+
+		// Disjoint-set over parts followed by symbols.
+		let n = self.parts.len();
+		let mut parent = (0..n + self.symbols.len()).collect::<Vec<_>>();
+		fn find(parent: &mut [usize], mut x: usize) -> usize {
+			while parent[x] != x {
+				parent[x] = parent[parent[x]];
+				x = parent[x];
+			}
+			x
+		}
+		for &(p, s) in &self.edges {
+			let a = find(&mut parent, p);
+			let b = find(&mut parent, n + s);
+			parent[a] = b;
+		}
+
+		let mut clusters = HashMap::new();
+		for i in 0..parent.len() {
+			let root = find(&mut parent, i);
+			let (p, s) = clusters.entry(root).or_insert((0, 0));
+			if i < n { *p += 1 } else { *s += 1 }
+		}
+		let largest_cluster = clusters
+			.into_values()
+			.max_by_key(|&(p, s)| (p + s, p))
+			.unwrap_or_default();
+
+		Stats { lonely_symbols, shared_parts, largest_cluster }
+	}
+}
+
+fn generate_dot(graph: &Graph) -> String {
+	let mut buf = String::with_capacity(graph.edges.len() * 16);
+
+	_ = writeln!(&mut buf, "graph {{");
+	for (i, p) in graph.parts.iter().enumerate() {
+		_ = writeln!(&mut buf, "    p{i} [label=\"{}\" shape=box]", p.num);
+	}
+	for (i, &(_, _, x)) in graph.symbols.iter().enumerate() {
+		_ = writeln!(&mut buf, "    s{i} [label={:?} shape=diamond]", (x as char).to_string());
+	}
+	for &(p, s) in &graph.edges {
+		_ = writeln!(&mut buf, "    p{p} -- s{s}");
+	}
+	_ = writeln!(&mut buf, "}}");
+
+	buf
+}
+
+fn generate_json(graph: &Graph, stats: &Stats) -> String {
+	let mut buf = String::with_capacity(graph.edges.len() * 32);
+
+	_ = writeln!(&mut buf, "{{");
+	_ = writeln!(&mut buf, "  \"parts\": [");
+	for (i, p) in graph.parts.iter().enumerate() {
+		let sep = if i + 1 == graph.parts.len() { "" } else { "," };
+		_ = writeln!(&mut buf, "    {{\"num\": {}, \"row\": {}, \"cols\": [{}, {}]}}{sep}", p.num, p.row, p.cols.start, p.cols.end);
+	}
+	_ = writeln!(&mut buf, "  ],");
+	_ = writeln!(&mut buf, "  \"symbols\": [");
+	for (i, &(r, c, x)) in graph.symbols.iter().enumerate() {
+		let sep = if i + 1 == graph.symbols.len() { "" } else { "," };
+		_ = writeln!(&mut buf, "    {{\"symbol\": {}, \"row\": {r}, \"col\": {c}}}{sep}", json_string(&(x as char).to_string()));
+	}
+	_ = writeln!(&mut buf, "  ],");
+	_ = write!(&mut buf, "  \"edges\": [");
+	for (i, &(p, s)) in graph.edges.iter().enumerate() {
+		let sep = if i == 0 { "" } else { ", " };
+		_ = write!(&mut buf, "{sep}[{p}, {s}]");
+	}
+	_ = writeln!(&mut buf, "],");
+	_ = writeln!(&mut buf, "  \"stats\": {{\"lonely_symbols\": {}, \"shared_parts\": {}, \"largest_cluster\": {{\"parts\": {}, \"symbols\": {}}}}}",
+		stats.lonely_symbols, stats.shared_parts, stats.largest_cluster.0, stats.largest_cluster.1);
+	_ = writeln!(&mut buf, "}}");
+
+	buf
+}
+
+fn parts(input: &str) -> Vec<Part> {
+	// Blank lines stay as empty rows, so rows match the input lines.
+	let m = input
+		.lines()
+		.map(|s| s.trim_end().as_bytes())
+		.collect::<Vec<_>>();
+	let h = m.len();
 
+	let mut parts = Vec::new();
+
+	for (r, row) in m.iter().enumerate() {
+		let w = row.len();
+		let mut c = 0;
+		while c < w {
+			if !row[c].is_ascii_digit() {
 				c += 1;
-				if c == w {
-					break;
-				}
+				continue;
 			}
 
-			if let Some((r, c)) = star {
-				let (k, m) = gears.entry((r, c)).or_insert((0, 1));
-				*k += 1;
-				if *k <= 2 {
-					*m *= num;
-				}
+			let start = c;
+			let mut num = 0;
+			while c < w && row[c].is_ascii_digit() {
+				num = num * 10 + (row[c] - b'0') as i32;
+				c += 1;
 			}
 
-			if good {
-				sum += num;
+			let mut symbols = Vec::new();
+			for (r, row) in m.iter().enumerate().take(h.min(r + 2)).skip(r.saturating_sub(1)) {
+				for (c, &x) in row.iter().enumerate().take(c + 1).skip(start.saturating_sub(1)) {
+					if is_symbol(x) {
+						symbols.push((r, c, x));
+					}
+				}
 			}
+
+			parts.push(Part { num, row: r, cols: start..c, symbols });
 		}
 	}
 
-	let gears = gears
-		.into_values()
-		.filter(|&(k, _)| k == 2)
-		.map(|(_, x)| x)
-		.sum();
+	parts
+}
 
-	(sum, gears)
+/// Quotes a string for JSON, whose escapes differ from what `{:?}` gives.
+fn json_string(s: &str) -> String {
+	let mut buf = String::with_capacity(s.len() + 2);
+	buf.push('"');
+	for c in s.chars() {
+		match c {
+			'"'          => buf.push_str("\\\""),
+			'\\'         => buf.push_str("\\\\"),
+			'\n'         => buf.push_str("\\n"),
+			'\r'         => buf.push_str("\\r"),
+			'\t'         => buf.push_str("\\t"),
+			c if c < ' ' => _ = write!(&mut buf, "\\u{:04x}", c as u32),
+			c            => buf.push(c),
+		}
+	}
+	buf.push('"');
+	buf
 }
 
 #[cfg(test)]
@@ -122,11 +356,107 @@ mod tests {
 
 	#[test]
 	fn test_part1() {
-		assert_eq!(solve(INPUT).0, 4361);
+		assert_eq!(solve(INPUT, &GEAR).0, 4361);
 	}
 
 	#[test]
 	fn test_part2() {
-		assert_eq!(solve(INPUT).1, 467835);
+		assert_eq!(solve(INPUT, &GEAR).1, 467835);
+	}
+
+	#[test]
+	fn test_wide() {
+		const INPUT: &str = r#"12.....*..7
+..#..45.100
+9.......*.."#;
+		assert_eq!(solve(INPUT, &GEAR), (12 + 45 + 100, 45 * 100));
+	}
+
+	#[test]
+	fn test_tall() {
+		const INPUT: &str = r#"1.
+.*
+23
+..
+4$
+.5
+6."#;
+		assert_eq!(solve(INPUT, &GEAR), (1 + 23 + 4 + 5, 23));
+	}
+
+	#[test]
+	fn test_parts() {
+		const INPUT: &str = r#"..12*
+3....
+.#..7"#;
+		assert_eq!(parts(INPUT), [
+			Part { num: 12, row: 0, cols: 2..4, symbols: vec![(0, 4, b'*')] },
+			Part { num: 3,  row: 1, cols: 0..1, symbols: vec![(2, 1, b'#')] },
+			Part { num: 7,  row: 2, cols: 4..5, symbols: vec![] },
+		]);
+	}
+
+	#[test]
+	fn test_gear_rules() {
+		const INPUT: &str = r#"2.3..
+.*.*.
+4.5..
+..6#."#;
+		assert_eq!(solve(INPUT, &GEAR).1, 3 * 5);
+		assert_eq!(solve(INPUT, &"*:4:product".parse().unwrap()).1, 2 * 3 * 4 * 5);
+		assert_eq!(solve(INPUT, &"*:2+:product".parse().unwrap()).1, 2 * 3 * 4 * 5 + 3 * 5);
+		assert_eq!(solve(INPUT, &"*:2+:sum".parse().unwrap()).1, 2 + 3 + 4 + 5 + 3 + 5);
+		assert_eq!(solve(INPUT, &"#:2:sum".parse().unwrap()).1, 5 + 6);
+	}
+
+	#[test]
+	fn test_gear_rule_parse() {
+		assert_eq!("*:2:product".parse(), Ok(GEAR));
+		assert_eq!("#:3+:sum".parse(), Ok(GearRule { symbol: b'#', count: Count::AtLeast(3), combine: Combine::Sum }));
+		assert!("**:2:sum".parse::<GearRule>().is_err());
+		assert!("*:x:sum".parse::<GearRule>().is_err());
+		assert!("*:2:max".parse::<GearRule>().is_err());
+		assert!("*:2".parse::<GearRule>().is_err());
+	}
+
+	#[test]
+	fn test_graph() {
+		let g = graph(INPUT);
+		assert_eq!(g.symbols.len(), 6);
+		assert_eq!(g.edges.len(), 8);
+		assert_eq!(g.stats(), Stats { lonely_symbols: 0, shared_parts: 0, largest_cluster: (2, 1) });
+
+		const SHARED: &str = r#"1.2..
+.*.*#
+..3.."#;
+		let g = graph(SHARED);
+		assert_eq!(g.edges, [(0, 0), (1, 0), (1, 1), (2, 0), (2, 1)]);
+		assert_eq!(g.stats(), Stats { lonely_symbols: 1, shared_parts: 2, largest_cluster: (3, 2) });
+
+		let dot = generate_dot(&g);
+		assert!(dot.contains("    s2 [label=\"#\" shape=diamond]\n"));
+		assert!(dot.contains("    p2 -- s1\n"));
+
+		let json = generate_json(&g, &g.stats());
+		assert!(json.contains("  \"edges\": [[0, 0], [1, 0], [1, 1], [2, 0], [2, 1]],\n"));
+		assert!(json.contains("\"largest_cluster\": {\"parts\": 3, \"symbols\": 2}"));
+
+		assert_eq!(json_string("\"\\\n\x1b*"), r#""\"\\\n\u001b*""#);
+		let json = generate_json(&graph("1\"\n"), &graph("1\"\n").stats());
+		assert!(json.contains(r#"{"symbol": "\"", "row": 0, "col": 1}"#));
+	}
+
+	#[test]
+	fn test_blank_lines() {
+		// Rows keep their input line numbers, and nothing touches across a gap.
+		let g = graph("\n1*\n");
+		assert_eq!(g.parts, [Part { num: 1, row: 1, cols: 0..1, symbols: vec![(1, 1, b'*')] }]);
+		assert_eq!(g.edges, [(0, 0)]);
+
+		let g = graph("1.\n\n2*\n");
+		assert_eq!(g.parts.iter().map(|p| (p.num, p.row)).collect::<Vec<_>>(), [(1, 0), (2, 2)]);
+		assert_eq!(g.edges, [(1, 0)]);
+		assert_eq!(g.stats().lonely_symbols, 0);
+		assert_eq!(solve("1.\n\n2*\n", &GEAR), (2, 0));
 	}
 }
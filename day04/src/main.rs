@@ -1,3 +1,8 @@
+use std::fs;
+use std::env;
+use std::fmt::Write;
+use std::hint::black_box;
+use std::time::Instant;
 use std::collections::HashSet;
 use std::io::{self, Read};
 
@@ -8,54 +13,216 @@ fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let (p1, p2) = solve(&input);
+	let mut rules    = RULES;
+	let mut timeline = false;
+	for arg in env::args().skip(1) {
+		match arg.as_str() {
+			"bench"    => bench(&input),
+			"timeline" => timeline = true,
+			"doubling" => rules.scoring  = Scoring::Doubling,
+			"linear"   => rules.scoring  = Scoring::Linear,
+			"cap"      => rules.overflow = Overflow::Cap,
+			"wrap"     => rules.overflow = Overflow::Wrap,
+			"error"    => rules.overflow = Overflow::Error,
+			_ => {
+				let Some(offset) = arg.strip_prefix("offset=") else {
+					return Err(format!("Unknown argument '{arg}'").into());
+				};
+				rules.offset = offset.parse()?;
+			},
+		}
+	}
+
+	if timeline {
+		let entries = copy_timeline(&matches(&parse(&input)), &rules)?;
+		fs::write("timeline.csv", generate_csv(&entries))?;
+		println!("Saved a card-by-card copy timeline to timeline.csv");
+	}
+
+	let (p1, p2) = solve(&input, &rules)?;
 	println!("p1 = {p1}");
 	println!("p2 = {p2}");
 
 	Ok(())
 }
 
-fn solve(input: &str) -> (i32, i32) {
-	let matches = input
+type Card = (Vec<u32>, Vec<u32>);
+
+#[derive(Debug, Clone, Copy)]
+enum Scoring {
+	/// First match is worth 1 point, every next one doubles it.
+	Doubling,
+	/// Every match is worth 1 point.
+	Linear,
+}
+
+/// What happens to copies won past the last card.
+#[derive(Debug, Clone, Copy)]
+enum Overflow {
+	/// Dropped, as if the table ended there.
+	Cap,
+	/// Go around to the start of the table. Those are only counted once all
+	/// cards are done, so wrapped copies never win anything themselves.
+	Wrap,
+	Error,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rules {
+	scoring:  Scoring,
+	/// Distance from a card to the first one of its `m` won copies.
+	offset:   usize,
+	overflow: Overflow,
+}
+
+const RULES: Rules = Rules { scoring: Scoring::Doubling, offset: 1, overflow: Overflow::Error };
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+	matches: usize,
+	points:  i32,
+	copies:  i32,
+	/// Cards which gave copies of this one as `(card, copies)`.
+	from:    Vec<(usize, i32)>,
+}
+
+fn solve(input: &str, rules: &Rules) -> Result<(i32, i32)> {
+	let entries = copy_timeline(&matches(&parse(input)), rules)?;
+	let points  = entries.iter().map(|e| e.points).sum();
+	let copies  = entries.iter().map(|e| e.copies).sum();
+	Ok((points, copies))
+}
+
+fn copy_timeline(matches: &[usize], rules: &Rules) -> Result<Vec<Entry>> {
+	let n = matches.len();
+
+	let mut entries = matches
+		.iter()
+		.map(|&m| Entry { matches: m, points: 0, copies: 1, from: Vec::new() })
+		.collect::<Vec<_>>();
+
+	let mut wrapped = Vec::new();
+	for (c, &m) in matches.iter().enumerate() {
+		if m == 0 {
+			continue;
+		}
+
+		entries[c].points = match rules.scoring {
+			Scoring::Doubling => 1 << (m - 1),
+			Scoring::Linear   => m as i32,
+		};
+
+		let have = entries[c].copies;
+		for i in 0..m {
+			let won = (c + i).checked_add(rules.offset);
+			let won = match (won, rules.overflow) {
+				(Some(won), _) if won < n    => won,
+				(_, Overflow::Cap)           => break,
+				(_, Overflow::Wrap)          => {
+					wrapped.push((((c + i) % n + rules.offset % n) % n, c, have));
+					continue;
+				},
+				(Some(won), Overflow::Error) => return Err(format!("Card {} wins a copy of card {}, but there are only {n} cards", c + 1, won as u128 + 1).into()),
+				(None, Overflow::Error)      => return Err(format!("Card {} wins a copy {} cards past it, which is beyond any table", c + 1, rules.offset).into()),
+			};
+			entries[won].copies += have;
+			entries[won].from.push((c, have));
+		}
+	}
+
+	for (won, c, have) in wrapped {
+		entries[won].copies += have;
+		entries[won].from.push((c, have));
+	}
+
+	Ok(entries)
+}
+
+fn generate_csv(entries: &[Entry]) -> String {
+	let mut buf = String::with_capacity(entries.len() * 32);
+
+	_ = writeln!(&mut buf, "card,matches,points,copies,from");
+	for (c, e) in entries.iter().enumerate() {
+		_ = write!(&mut buf, "{},{},{},{},", c + 1, e.matches, e.points, e.copies);
+		for (i, &(from, k)) in e.from.iter().enumerate() {
+			let sep = if i == 0 { "" } else { " " };
+			_ = write!(&mut buf, "{sep}{}:{k}", from + 1);
+		}
+		_ = writeln!(&mut buf);
+	}
+
+	buf
+}
+
+fn parse(input: &str) -> Vec<Card> {
+	input
 		.lines()
 		.filter_map(|s| {
 			let (_, s)       = s.split_once(':')?;
 			let (wins, mine) = s.split_once('|')?;
 
-			fn parse_numbers(s: &str) -> HashSet<i32> {
+			fn parse_numbers(s: &str) -> Vec<u32> {
 				s
-					.trim()
-					.split(' ')
+					.split_whitespace()
 					.filter_map(|w| w.parse().ok())
 					.collect()
 			}
 
-			let wins = parse_numbers(wins);
-			let mine = parse_numbers(mine);
-			Some(wins.intersection(&mine).count())
+			Some((parse_numbers(wins), parse_numbers(mine)))
 		})
-		.collect::<Vec<_>>();
+		.collect()
+}
 
-	let mut points = 0;
-	let mut copies = vec![1; matches.len()];
+/// Counts matches per card, via bitsets when every number fits in 128 bits.
+fn matches(cards: &[Card]) -> Vec<usize> {
+	let max = cards
+		.iter()
+		.flat_map(|(wins, mine)| wins.iter().chain(mine))
+		.max()
+		.copied()
+		.unwrap_or_default();
+	if max < u128::BITS {
+		matches_bits(cards)
+	} else {
+		matches_set(cards)
+	}
+}
 
-	for (c, &m) in matches.iter().enumerate() {
-		if m == 0 {
-			continue;
-		}
+fn matches_bits(cards: &[Card]) -> Vec<usize> {
+	fn bits(s: &[u32]) -> u128 {
+		s.iter().fold(0, |acc, &x| acc | 1 << x)
+	}
 
-		points += 1 << (m - 1);
+	cards
+		.iter()
+		.map(|(wins, mine)| (bits(wins) & bits(mine)).count_ones() as usize)
+		.collect()
+}
 
-		let have = copies[c];
-		for i in 0..m {
-			let won = c + i + 1;
-			copies[won] += have;
-		}
-	}
+fn matches_set(cards: &[Card]) -> Vec<usize> {
+	cards
+		.iter()
+		.map(|(wins, mine)| {
+			let wins = wins.iter().collect::<HashSet<_>>();
+			let mine = mine.iter().collect::<HashSet<_>>();
+			wins.intersection(&mine).count()
+		})
+		.collect()
+}
 
-	let copies = copies.into_iter().sum();
+fn bench(input: &str) {
+	const N: u32 = 1000;
 
-	(points, copies)
+	let cards = parse(input);
+	let run = |name: &str, f: fn(&[Card]) -> Vec<usize>| {
+		let start = Instant::now();
+		for _ in 0..N {
+			black_box(f(black_box(&cards)));
+		}
+		println!("{name:>4}: {:?} per {} cards", start.elapsed() / N, cards.len());
+	};
+	run("bits", matches_bits);
+	run("set",  matches_set);
 }
 
 #[cfg(test)]
@@ -71,11 +238,73 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11"#;
 
 	#[test]
 	fn test_part1() {
-		assert_eq!(solve(INPUT).0, 13);
+		assert_eq!(solve(INPUT, &RULES).unwrap().0, 13);
 	}
 
 	#[test]
 	fn test_part2() {
-		assert_eq!(solve(INPUT).1, 30);
+		assert_eq!(solve(INPUT, &RULES).unwrap().1, 30);
+	}
+
+	#[test]
+	fn test_matches() {
+		let cards = parse(INPUT);
+		assert_eq!(matches_bits(&cards), [4, 2, 2, 1, 0, 0]);
+		assert_eq!(matches_set(&cards),  [4, 2, 2, 1, 0, 0]);
+
+		let cards = parse("Card 1: 1 200 3 | 200 1 7\nCard 2: 127 0 | 127 0");
+		assert_eq!(matches(&cards), [2, 2]);
+		assert_eq!(matches_bits(&cards[1..]), [2]);
+	}
+
+	#[test]
+	fn test_rules() {
+		let rules = Rules { scoring: Scoring::Linear, ..RULES };
+		assert_eq!(solve(INPUT, &rules).unwrap(), (4 + 2 + 2 + 1, 30));
+
+		let rules = Rules { offset: 2, overflow: Overflow::Cap, ..RULES };
+		assert_eq!(solve(INPUT, &rules).unwrap(), (13, 1 + 1 + 2 + 3 + 5 + 7));
+	}
+
+	#[test]
+	fn test_overflow() {
+		const INPUT: &str = r#"Card 1: 1 2 | 3 4
+Card 2: 1 2 | 1 4
+Card 3: 1 2 3 | 1 2 3"#;
+		assert!(solve(INPUT, &RULES).is_err());
+
+		let rules = Rules { overflow: Overflow::Cap, ..RULES };
+		assert_eq!(solve(INPUT, &rules).unwrap(), (1 + 4, 1 + 1 + 2));
+
+		let rules = Rules { overflow: Overflow::Wrap, ..RULES };
+		assert_eq!(solve(INPUT, &rules).unwrap(), (1 + 4, 1 + 1 + 2 + 2 * 3));
+
+		// Wrapping onto a card not yet processed still doesn't win more copies.
+		const LATER: &str = r#"Card 1: 1 2 | 1 3
+Card 2: 5 | 5
+Card 3: 7 | 8"#;
+		let rules = Rules { offset: 4, overflow: Overflow::Wrap, ..RULES };
+		let entries = copy_timeline(&matches(&parse(LATER)), &rules).unwrap();
+		assert_eq!(entries.iter().map(|e| e.copies).collect::<Vec<_>>(), [1, 2, 2]);
+		assert_eq!(entries[2].from, [(1, 1)]);
+
+		// Offsets past `usize` go through the same policy.
+		let rules = Rules { offset: usize::MAX, ..RULES };
+		assert!(solve(LATER, &rules).is_err());
+		assert_eq!(solve(LATER, &Rules { overflow: Overflow::Cap, ..rules }).unwrap(), (2, 3));
+		assert_eq!(solve(LATER, &Rules { overflow: Overflow::Wrap, ..rules }).unwrap(), (2, 5));
+	}
+
+	#[test]
+	fn test_timeline() {
+		let entries = copy_timeline(&matches(&parse(INPUT)), &RULES).unwrap();
+		assert_eq!(entries[3], Entry { matches: 1, points: 1, copies: 8, from: vec![(0, 1), (1, 2), (2, 4)] });
+
+		let csv = generate_csv(&entries);
+		let mut lines = csv.lines();
+		assert_eq!(lines.next(), Some("card,matches,points,copies,from"));
+		assert_eq!(lines.next(), Some("1,4,8,1,"));
+		assert_eq!(lines.next(), Some("2,2,2,2,1:1"));
+		assert_eq!(lines.nth(2), Some("5,0,0,14,1:1 3:4 4:8"));
 	}
 }
@@ -1,5 +1,8 @@
 #![feature(iter_array_chunks)]
 
+use std::env;
+use std::fmt;
+use std::ops::Range;
 use std::io::{self, Read};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -9,98 +12,369 @@ fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let (seeds, layers) = parse(&input).expect("Bad input");
-	let p1 = part1(&seeds, &layers);
+	let mut almanac = parse(&input)?;
+
+	let mut args = env::args().skip(1).collect::<Vec<_>>();
+	if let Some(i) = args.iter().position(|a| a.starts_with("overlap=")) {
+		almanac.overlap = match &args.remove(i)["overlap=".len()..] {
+			"first" => Overlap::FirstWins,
+			"last"  => Overlap::LastWins,
+			"error" => Overlap::Error,
+			s => return Err(format!("Unknown overlap policy '{s}', expected first, last or error").into()),
+		};
+	}
+
+	let issues = almanac.validate();
+	for issue in &issues {
+		eprintln!("warning: {issue}");
+	}
+	if issues.iter().any(|i| i.is_fatal(almanac.overlap)) {
+		return Err("Almanac is not valid".into());
+	}
+
+	let map = almanac.map("seed", "location")?;
+
+	if !args.is_empty() {
+		for arg in args {
+			let (from, to, q) = match arg.split_once('=') {
+				Some((cats, q)) => {
+					let (from, to) = cats.split_once(':').ok_or("Query should look like <from>:<to>=<value>")?;
+					(from, to, q)
+				},
+				None => ("seed", "location", arg.as_str()),
+			};
+			let r = parse_query(q)?;
+			let mapped = almanac.query(from, to, r.clone())?;
+			if r.end - r.start == 1 {
+				println!("{from} {} -> {to} {:?}", r.start, mapped.into_iter().flatten().collect::<Vec<_>>());
+			} else {
+				println!("{from} {r:?} -> {to} {mapped:?}");
+			}
+		}
+		return Ok(());
+	}
+
+	let seeds = &almanac.seeds;
+	let p1 = part1(seeds, &map);
 	println!("p1 = {p1}");
-	let p2 = part2(&seeds, &layers);
+	let p2 = part2(seeds, &map);
+	if part2_seed(seeds, &map, p2).is_none() {
+		return Err(format!("Location {p2} doesn't map back into any seed range").into());
+	}
 	println!("p2 = {p2}");
 
 	Ok(())
 }
 
-fn parse(input: &str) -> Option<(Vec<i64>, Vec<Vec<(i64, i64, i64)>>)> {
-	let mut sections = input.split("\n\n");
+/// Lines of a map section as `(line, dst, src, n)`.
+type Layer = Vec<(usize, i64, i64, i64)>;
+
+/// Which line maps a value covered by multiple lines of the same section.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Overlap {
+	FirstWins,
+	LastWins,
+	/// Overlaps fail the validation, first line wins if mapped anyway.
+	Error,
+}
+
+#[derive(Debug, PartialEq)]
+enum Issue {
+	Overlap  { line: usize, with: usize },
+	Empty    { line: usize },
+	Overflow { line: usize },
+}
+
+impl Issue {
+	fn is_fatal(&self, overlap: Overlap) -> bool {
+		match self {
+			Issue::Overlap  { .. } => overlap == Overlap::Error,
+			Issue::Empty    { .. } => false,
+			Issue::Overflow { .. } => true,
+		}
+	}
+}
+
+impl fmt::Display for Issue {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Issue::Overlap  { line, with } => write!(f, "line {line} overlaps source range of line {with}"),
+			Issue::Empty    { line }       => write!(f, "line {line} has an empty range"),
+			Issue::Overflow { line }       => write!(f, "line {line} range overflows"),
+		}
+	}
+}
+
+/// Checks lines of a single section.
+fn validate(layer: &[(usize, i64, i64, i64)]) -> Vec<Issue> {
+	let mut issues = Vec::new();
+	let mut ranges: Vec<(usize, Range<i64>)> = Vec::with_capacity(layer.len());
+	for &(line, dst, src, n) in layer {
+		if n <= 0 {
+			issues.push(Issue::Empty { line });
+		} else if src.checked_add(n).is_none() || dst.checked_add(n).is_none() {
+			issues.push(Issue::Overflow { line });
+		} else {
+			let r = src..src + n;
+			for (with, prev) in &ranges {
+				if r.start < prev.end && prev.start < r.end {
+					issues.push(Issue::Overlap { line, with: *with });
+				}
+			}
+			ranges.push((line, r));
+		}
+	}
+	issues
+}
+
+/// Piecewise-linear function as sorted `(start, offset)` pieces, where a
+/// piece lasts until the start of the next one. The first piece starts at
+/// `i64::MIN` and the last one is open-ended, both having a zero offset.
+#[derive(Debug, PartialEq)]
+struct Map {
+	pieces: Vec<(i64, i64)>,
+}
+
+impl Map {
+	fn identity() -> Self {
+		Map { pieces: vec![(i64::MIN, 0)] }
+	}
+
+	/// Builds a map of a section, skipping invalid lines.
+	fn from_layer(layer: &[(usize, i64, i64, i64)], overlap: Overlap) -> Self {
+		let mut map = Map::identity();
+		let lines: Box<dyn Iterator<Item = _>> = match overlap {
+			Overlap::LastWins => Box::new(layer.iter()),
+			_                 => Box::new(layer.iter().rev()),
+		};
+		for &(_, dst, src, n) in lines {
+			let (Some(end), Some(_)) = (src.checked_add(n), dst.checked_add(n)) else {
+				continue;
+			};
+			if n > 0 {
+				map.paint(src..end, dst - src);
+			}
+		}
+		map
+	}
+
+	/// Sets the offset of a range, overriding what was there.
+	fn paint(&mut self, r: Range<i64>, offset: i64) {
+		let after = self.map(r.end) - r.end;
+		self.pieces.retain(|&(s, _)| s < r.start || s > r.end);
+		self.pieces.push((r.start, offset));
+		self.pieces.push((r.end, after));
+		self.normalize();
+	}
+
+	/// Sorts by start keeping the last of equal ones, then merges equal neighbours.
+	fn normalize(&mut self) {
+		self.pieces.reverse();
+		self.pieces.sort_by_key(|&(s, _)| s);
+		self.pieces.dedup_by_key(|&mut (s, _)| s);
+		self.pieces.dedup_by_key(|&mut (_, o)| o);
+	}
+
+	fn piece(&self, i: usize) -> (Range<i64>, i64) {
+		let (start, offset) = self.pieces[i];
+		let end = self.pieces.get(i + 1).map_or(i64::MAX, |&(s, _)| s);
+		(start..end, offset)
+	}
+
+	fn map(&self, x: i64) -> i64 {
+		let i = self.pieces.partition_point(|&(s, _)| s <= x) - 1;
+		x + self.pieces[i].1
+	}
+
+	/// Maps a range, splitting it only at the breakpoints it spans.
+	fn map_range(&self, r: Range<i64>) -> Vec<Range<i64>> {
+		let first = self.pieces.partition_point(|&(s, _)| s <= r.start) - 1;
+		(first..self.pieces.len())
+			.map(|i| self.piece(i))
+			.take_while(|(p, _)| p.start < r.end)
+			.map(|(p, o)| p.start.max(r.start) + o..p.end.min(r.end) + o)
+			.filter(|r| !r.is_empty())
+			.collect()
+	}
+
+	/// Gives all ranges which are mapped into `r`, sorted and merged.
+	fn unmap_range(&self, r: Range<i64>) -> Vec<Range<i64>> {
+		let mut ranges = (0..self.pieces.len())
+			.map(|i| self.piece(i))
+			.map(|(p, o)| p.start.max(r.start - o)..p.end.min(r.end - o))
+			.filter(|r| !r.is_empty())
+			.collect::<Vec<_>>();
+		ranges.sort_by_key(|r| r.start);
+
+		let mut merged: Vec<Range<i64>> = Vec::with_capacity(ranges.len());
+		for r in ranges {
+			match merged.last_mut() {
+				Some(last) if last.end >= r.start => last.end = last.end.max(r.end),
+				_ => merged.push(r),
+			}
+		}
+		merged
+	}
+
+	/// Gives a map doing `self` followed by `next`.
+	fn then(&self, next: &Map) -> Map {
+		let mut pieces = Vec::with_capacity(self.pieces.len() + next.pieces.len());
+		for i in 0..self.pieces.len() {
+			// Only bounded pieces have an offset, so there is no overflow.
+			let (p, o) = self.piece(i);
+			let (start, end) = (p.start + o, p.end + o);
+			for j in next.pieces.partition_point(|&(s, _)| s <= start) - 1..next.pieces.len() {
+				let (q, k) = next.piece(j);
+				if q.start >= end {
+					break;
+				}
+				pieces.push((q.start.max(start) - o, o + k));
+			}
+		}
+
+		let mut map = Map { pieces };
+		map.normalize();
+		map
+	}
+}
+
+fn compose<'a>(layers: impl IntoIterator<Item = &'a Layer>, overlap: Overlap) -> Map {
+	layers
+		.into_iter()
+		.fold(Map::identity(), |map, layer| map.then(&Map::from_layer(layer, overlap)))
+}
+
+/// Parses `79` as a single value range or `55..68` as is.
+fn parse_query(s: &str) -> Result<Range<i64>> {
+	match s.split_once("..") {
+		Some((start, end)) => Ok(start.parse()?..end.parse()?),
+		None => {
+			let x = s.parse()?;
+			Ok(x..x + 1)
+		},
+	}
+}
+
+struct Almanac<'a> {
+	seeds:    Vec<i64>,
+	overlap:  Overlap,
+	/// Sections as `(from, to, layer)` in the input order.
+	sections: Vec<(&'a str, &'a str, Layer)>,
+}
+
+impl Almanac<'_> {
+	/// Follows sections from one category to another.
+	fn path(&self, from: &str, to: &str) -> Result<Vec<&Layer>> {
+		let mut path = Vec::new();
+		let mut at   = from;
+		while at != to {
+			if path.len() == self.sections.len() {
+				return Err(format!("Categories loop around '{at}' on the way from '{from}' to '{to}'").into());
+			}
+			let (_, next, layer) = self
+				.sections
+				.iter()
+				.find(|(src, _, _)| *src == at)
+				.ok_or_else(|| format!("Chain from '{from}' to '{to}' is broken at '{at}'"))?;
+			path.push(layer);
+			at = next;
+		}
+		Ok(path)
+	}
+
+	fn map(&self, from: &str, to: &str) -> Result<Map> {
+		Ok(compose(self.path(from, to)?, self.overlap))
+	}
+
+	fn validate(&self) -> Vec<Issue> {
+		self
+			.sections
+			.iter()
+			.flat_map(|(_, _, layer)| validate(layer))
+			.collect()
+	}
 
-	let seeds = sections
-		.next()?
-		.strip_prefix("seeds: ")?
+	/// Maps a range between two categories, going backwards if needed.
+	fn query(&self, from: &str, to: &str, r: Range<i64>) -> Result<Vec<Range<i64>>> {
+		match self.map(from, to) {
+			Ok(map) => Ok(map.map_range(r)),
+			Err(e)  => self.map(to, from).map(|map| map.unmap_range(r)).map_err(|_| e),
+		}
+	}
+}
+
+fn parse(input: &str) -> Result<Almanac<'_>> {
+	let mut lines = input.lines();
+
+	let seeds = lines
+		.next()
+		.and_then(|s| s.strip_prefix("seeds: "))
+		.ok_or("Almanac should start with seeds")?
 		.split(' ')
 		.filter_map(|s| s.parse().ok())
 		.collect();
 
-	let layers = sections
-		.map(|s| s
-			 .lines()
-			 .skip(1)
-			 .filter_map(|s| {
-				 let [d, s, n] = s
-					 .split(' ')
-					 .filter_map(|x| x.parse().ok())
-					 .array_chunks()
-					 .next()?;
-				 Some((d, s, n))
-			 })
-			 .collect()
-		)
-		.collect();
+	let mut sections: Vec<(&str, &str, Layer)> = Vec::new();
+	for (i, s) in lines.enumerate() {
+		let line = i + 2;
+		if s.is_empty() {
+			continue;
+		}
+
+		if let Some(header) = s.strip_suffix(" map:") {
+			let (from, to) = header
+				.split_once("-to-")
+				.ok_or_else(|| format!("Bad section header '{s}' at line {line}"))?;
+			if sections.iter().any(|(src, _, _)| *src == from) {
+				return Err(format!("Category '{from}' is mapped more than once at line {line}").into());
+			}
+			sections.push((from, to, Vec::new()));
+			continue;
+		}
 
-	Some((seeds, layers))
+		let [d, s, n] = s
+			.split(' ')
+			.filter_map(|x| x.parse().ok())
+			.array_chunks()
+			.next()
+			.ok_or_else(|| format!("Bad map line '{s}' at line {line}"))?;
+		let (_, _, layer) = sections
+			.last_mut()
+			.ok_or_else(|| format!("Map line {line} is outside of any section"))?;
+		layer.push((line, d, s, n));
+	}
+
+	Ok(Almanac { seeds, overlap: Overlap::FirstWins, sections })
 }
 
-fn part1(seeds: &[i64], layers: &[Vec<(i64, i64, i64)>]) -> i64 {
+fn part1(seeds: &[i64], map: &Map) -> i64 {
 	seeds
 		.iter()
-		.map(|&s|
-			 layers
-			 .iter()
-			 .fold(s, |s, layer| {
-				 layer
-					 .iter()
-					 .find(|&&(_, src, n)| (src..src + n).contains(&s))
-					 .map(|&(dst, src, _)| dst + s - src)
-					 .unwrap_or(s)
-			 })
-		)
+		.map(|&s| map.map(s))
 		.min()
 		.unwrap_or_default()
 }
 
-fn part2(seeds: &[i64], layers: &[Vec<(i64, i64, i64)>]) -> i64 {
-	let seeds = seeds
+fn part2(seeds: &[i64], map: &Map) -> i64 {
+	seeds
 		.chunks_exact(2)
-		.map(|c| (c[0], c[0] +  c[1]))
-		.collect::<Vec<_>>();
-	layers
-		.iter()
-		.fold(seeds, |seeds, layer|
-			  seeds
-			  	.iter()
-			  	.flat_map(|&(start, end)| {
-					let mut mapped   = Vec::new();
-					let mut unmapped = vec![(start, end)];
-					for &(dst, src, n) in layer {
-						let mut temp = Vec::new();
-						for (start, end) in unmapped {
-							let p = (start, end.min(src));
-							let f = (start.max(src), (src + n).min(end));
-							let s = ((src + n).max(start), end);
-							if p.0 < p.1 { temp.push(p); } // prefix is unmapped
-							if f.0 < f.1 { mapped.push((f.0 - src + dst, f.1 - src + dst)); } // fully mapped
-							if s.0 < s.1 { temp.push(s); } // suffix is unmapped
-						}
-						unmapped = temp;
-					}
-					mapped.extend(unmapped);
-					mapped
-				})
-			  .collect()
-		)
-		.into_iter()
-		.map(|r| r.0)
+		.flat_map(|c| map.map_range(c[0]..c[0] + c[1]))
+		.map(|r| r.start)
 		.min()
 		.unwrap_or_default()
 }
 
+/// Finds a seed from the seed ranges which ends up at `location`.
+fn part2_seed(seeds: &[i64], map: &Map, location: i64) -> Option<i64> {
+	let origins = map.unmap_range(location..location + 1);
+	seeds
+		.chunks_exact(2)
+		.flat_map(|c| origins.iter().map(move |r| r.start.max(c[0])..r.end.min(c[0] + c[1])))
+		.filter(|r| !r.is_empty())
+		.map(|r| r.start)
+		.min()
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -141,13 +415,137 @@ humidity-to-location map:
 
 	#[test]
 	fn test_part1() {
-		let (seeds, layers) = parse(INPUT).unwrap();
-		assert_eq!(part1(&seeds, &layers), 35);
+		let almanac = parse(INPUT).unwrap();
+		assert_eq!(part1(&almanac.seeds, &almanac.map("seed", "location").unwrap()), 35);
 	}
 
 	#[test]
 	fn test_part2() {
-		let (seeds, layers) = parse(INPUT).unwrap();
-		assert_eq!(part2(&seeds, &layers), 46);
+		let almanac = parse(INPUT).unwrap();
+		assert_eq!(part2(&almanac.seeds, &almanac.map("seed", "location").unwrap()), 46);
+	}
+
+	fn fold(layers: &[&Layer], x: i64) -> i64 {
+		layers.iter().fold(x, |x, layer| Map::from_layer(layer, Overlap::FirstWins).map(x))
+	}
+
+	#[test]
+	fn test_compose() {
+		let almanac = parse(INPUT).unwrap();
+		let layers  = almanac.path("seed", "location").unwrap();
+		let map = compose(layers.iter().copied(), Overlap::FirstWins);
+		for x in -5..120 {
+			assert_eq!(map.map(x), fold(&layers, x), "seed {x}");
+		}
+		assert_eq!(map.map(79), 82);
+		assert_eq!(map.map(i64::MIN), i64::MIN);
+		assert_eq!(map.map(i64::MAX - 1), i64::MAX - 1);
+		assert!(map.pieces.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 != w[1].1));
+	}
+
+	#[test]
+	fn test_map_range() {
+		let map = parse(INPUT).unwrap().map("seed", "location").unwrap();
+		let mapped = map.map_range(79..93);
+		assert_eq!(mapped.iter().map(|r| r.end - r.start).sum::<i64>(), 14);
+		for x in 79..93 {
+			assert!(mapped.iter().any(|r| r.contains(&map.map(x))));
+		}
+		assert_eq!(Map::from_layer(&[(1, 50, 98, 2), (2, 52, 50, 48)], Overlap::FirstWins).map_range(40..100), [40..50, 52..100, 50..52]);
+	}
+
+	#[test]
+	fn test_unmap() {
+		let almanac = parse(INPUT).unwrap();
+		let (seeds, map) = (&almanac.seeds, almanac.map("seed", "location").unwrap());
+		for x in -5..120 {
+			let origins = map.unmap_range(x..x + 1);
+			assert!(origins.iter().flat_map(|r| r.clone()).all(|s| map.map(s) == x));
+			assert!(origins.iter().any(|r| r.contains(&x)) || map.map(x) != x);
+		}
+
+		assert_eq!(map.unmap_range(35..36).into_iter().flatten().collect::<Vec<_>>(), [13]);
+		assert_eq!(map.unmap_range(13..14).into_iter().flatten().collect::<Vec<_>>(), [38]);
+
+		let ranges = map.unmap_range(46..60);
+		assert_eq!(ranges.iter().flat_map(|r| r.clone()).count(), 14);
+		assert!(ranges.windows(2).all(|w| w[0].end < w[1].start));
+
+		assert_eq!(part2_seed(seeds, &map, 46), Some(82));
+		assert_eq!(part2_seed(seeds, &map, 35), None);
+	}
+
+	#[test]
+	fn test_categories() {
+		let almanac = parse(INPUT).unwrap();
+		let layers  = almanac.path("soil", "humidity").unwrap();
+		assert_eq!(layers.len(), 5);
+		let map = almanac.map("soil", "humidity").unwrap();
+		for x in 0..120 {
+			assert_eq!(map.map(x), fold(&layers, x));
+		}
+		assert_eq!(almanac.query("soil", "humidity", 81..82).unwrap().into_iter().flatten().collect::<Vec<_>>(), [78]);
+		assert!(almanac.query("humidity", "soil", 78..79).unwrap().iter().any(|r| r.contains(&81)));
+		assert!(almanac.map("seed", "seed").unwrap().pieces.len() == 1);
+		assert!(almanac.map("seed", "sunlight").is_err());
+
+		let mut sections = INPUT.split("\n\n").collect::<Vec<_>>();
+		sections[1..].reverse();
+		sections.swap(2, 5);
+		let shuffled = sections.join("\n\n");
+		let shuffled = parse(&shuffled).unwrap();
+		let map = shuffled.map("seed", "location").unwrap();
+		assert_eq!(part1(&shuffled.seeds, &map), 35);
+		assert_eq!(part2(&shuffled.seeds, &map), 46);
+
+		let broken = INPUT.replace("water-to-light", "water-to-lite");
+		assert!(parse(&broken).unwrap().map("seed", "location").is_err());
+
+		let looped = INPUT.replace("humidity-to-location", "humidity-to-soil");
+		assert!(parse(&looped).unwrap().map("seed", "location").is_err());
+
+		let twice = INPUT.replace("water-to-light", "seed-to-light");
+		assert!(parse(&twice).is_err());
+
+		assert!(parse(&INPUT.replace("seed-to-soil map:", "seed to soil map:")).is_err());
+		assert!(parse(&INPUT.replace("50 98 2", "50 98")).is_err());
+	}
+
+	#[test]
+	fn test_validate() {
+		let almanac = parse(INPUT).unwrap();
+		assert_eq!(almanac.validate(), []);
+		assert_eq!(almanac.sections[0].2, [(4, 50, 98, 2), (5, 52, 50, 48)]);
+
+		const BAD: &str = r#"seeds: 7 2
+
+a-to-b map:
+100 0 10
+200 5 10
+300 50 0
+400 9223372036854775800 10
+
+b-to-c map:
+0 0 1000"#;
+		let mut almanac = parse(BAD).unwrap();
+		let issues = almanac.validate();
+		assert_eq!(issues, [
+			Issue::Overlap { line: 5, with: 4 },
+			Issue::Empty { line: 6 },
+			Issue::Overflow { line: 7 },
+		]);
+		assert_eq!(issues[0].to_string(), "line 5 overlaps source range of line 4");
+		assert!(!issues[0].is_fatal(Overlap::FirstWins));
+		assert!(issues[0].is_fatal(Overlap::Error));
+		assert!(issues[2].is_fatal(Overlap::LastWins));
+
+		let map = almanac.map("a", "c").unwrap();
+		assert_eq!((map.map(5), map.map(9), map.map(10), map.map(50)), (105, 109, 205, 50));
+		assert_eq!((part1(&almanac.seeds, &map), part2(&almanac.seeds, &map)), (102, 107));
+
+		almanac.overlap = Overlap::LastWins;
+		let map = almanac.map("a", "c").unwrap();
+		assert_eq!((map.map(4), map.map(5), map.map(10)), (104, 200, 205));
+		assert_eq!((part1(&almanac.seeds, &map), part2(&almanac.seeds, &map)), (102, 202));
 	}
 }
@@ -1,3 +1,5 @@
+use std::env;
+use std::fmt::Write;
 use std::io::{self, Read};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -7,41 +9,202 @@ fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let p1 = part1(&input);
+	let mut race  = RACE;
+	let mut table = false;
+	for arg in env::args().skip(1) {
+		if arg == "table" {
+			table = true;
+			continue;
+		}
+		let (k, v) = arg.split_once('=').ok_or_else(|| format!("Expected <param>=<value>, got '{arg}'"))?;
+		match k {
+			"rate"  => race.rate  = v.parse()?,
+			"cap"   => race.cap   = Some(v.parse()?),
+			"decel" => race.decel = v.parse()?,
+			_       => return Err(format!("Unknown race parameter '{k}'").into()),
+		}
+	}
+
+	let races  = parse(&input, false).ok_or("Bad input")?;
+	let joined = parse(&input, true).ok_or("Bad input")?;
+
+	if table {
+		print!("{}", generate_table(&races, &race));
+		print!("{}", generate_table(&joined, &race));
+	}
+
+	let p1 = part1(&races, &race);
 	println!("p1 = {p1}");
-	let p2 = part2(&input);
+	let p2 = part2(&joined, &race);
 	println!("p2 = {p2}");
 
 	Ok(())
 }
 
-fn part1(input: &str) -> i64 {
-	let (time, dist) = input.split_once('\n').expect("Bad input");
+/// Reads `(time, distance)` races, `joined` ignores spaces between digits.
+fn parse(input: &str, joined: bool) -> Option<Vec<(u64, u64)>> {
+	let (time, dist) = input.split_once('\n')?;
+
+	let numbers = |s: &str, prefix: &str| -> Option<Vec<u64>> {
+		let s = s.trim().strip_prefix(prefix)?;
+		if joined {
+			Some(vec![s.split_whitespace().collect::<String>().parse().ok()?])
+		} else {
+			s.split_whitespace().map(|s| s.parse().ok()).collect()
+		}
+	};
+
+	let time = numbers(time, "Time:")?;
+	let dist = numbers(dist, "Distance:")?;
+	if time.len() != dist.len() {
+		return None;
+	}
+
+	Some(time.into_iter().zip(dist).collect())
+}
+
+fn part1(races: &[(u64, u64)], race: &Race) -> u64 {
+	races.iter().map(|&(t, d)| race.wins(t, d)).product()
+}
 
-	let time = time.split_whitespace().filter_map(|s| s.parse::<i64>().ok());
-	let dist = dist.split_whitespace().filter_map(|s| s.parse::<i64>().ok());
+/// Joined input is just a single race.
+fn part2(races: &[(u64, u64)], race: &Race) -> u64 {
+	races.first().map_or(0, |&(t, d)| race.wins(t, d))
+}
 
-	time.zip(dist).map(|(t, d)| solve(t as f64, d as f64)).product()
+/// Holding the button adds `rate` mm/ms of speed up to the `cap`, while
+/// moving the boat loses `decel` mm/ms every ms until it stops.
+#[derive(Debug, Clone, Copy)]
+struct Race {
+	rate:  u64,
+	cap:   Option<u64>,
+	decel: u64,
 }
 
-fn part2(input: &str) -> i64 {
-	let (time, dist) = input.split_once('\n').expect("Bad input");
+const RACE: Race = Race { rate: 1, cap: None, decel: 0 };
+
+impl Race {
+	/// Saturates at `u128::MAX`, which is still farther than any record.
+	fn dist(&self, t: u64, h: u64) -> u128 {
+		let v = (self.rate as u128 * h as u128).min(self.cap.map_or(u128::MAX, |c| c as u128));
+		let n = (t - h) as u128;
+		if self.decel == 0 {
+			return v.saturating_mul(n);
+		}
+		// Moving for `k` ms as `v, v - a, v - 2a, ...` with `a * (k - 1) < v`,
+		// summed as `k` times the last speed plus `a * (0 + 1 + .. + k - 1)`
+		// so that nothing is subtracted after saturating.
+		let a = self.decel as u128;
+		let k = n.min(v.div_ceil(a));
+		if k == 0 {
+			return 0;
+		}
+		let steps = k * (k - 1) / 2;
+		k.saturating_mul(v - a * (k - 1)).saturating_add(a.saturating_mul(steps))
+	}
+
+	/// Gives the first and the last winning hold times.
+	fn window(&self, t: u64, d: u64) -> Option<(u64, u64)> {
+		let capped = self.cap.is_some_and(|c| (c as u128) < self.rate as u128 * t as u128);
+		if !capped && self.decel == 0 && self.rate > 0 {
+			// `rate * x > d` is the same as `x > d / rate` for integers.
+			let lo = window(t, d / self.rate)?;
+			return Some((lo, t - lo));
+		}
 
-	fn read_number(s: &str) -> i64 {
-		s
-			.bytes()
-			.filter(|b| b.is_ascii_digit())
-			.fold(0, |a, x| a * 10 + (x - b'0') as i64)
+		// Distance rises up to the peak and never rises after it, so both
+		// halves are monotone and could be binary searched.
+		let peak = self.best(t);
+		if self.dist(t, peak) <= d as u128 {
+			return None;
+		}
+		let lo = partition_point(0, peak, |h| self.dist(t, h) <= d as u128);
+		let hi = partition_point(peak, t, |h| self.dist(t, h + 1) > d as u128);
+		Some((lo, hi))
 	}
 
-	let t = read_number(time);
-	let d = read_number(dist);
-	solve(t as f64, d as f64)
+	/// Gives the hold time going the farthest, the first one if many.
+	fn best(&self, t: u64) -> u64 {
+		partition_point(0, t, |h| self.dist(t, h) < self.dist(t, h + 1))
+	}
+
+	fn wins(&self, t: u64, d: u64) -> u64 {
+		self.window(t, d).map_or(0, |(lo, hi)| hi - lo + 1)
+	}
 }
 
-fn solve(t: f64, d: f64) -> i64 {
-	let x = ((t - (t * t - 4.0 * (d + 1.0)).sqrt()) * 0.5).ceil() as i64;
-	(t as i64) - 2 * x + 1
+fn generate_table(races: &[(u64, u64)], race: &Race) -> String {
+	let mut rows = vec![["time", "record", "ways", "first", "last", "best", "best dist"].map(String::from)];
+	for &(t, d) in races {
+		let (first, last) = race
+			.window(t, d)
+			.map_or(("-".into(), "-".into()), |(lo, hi)| (lo.to_string(), hi.to_string()));
+		let best = race.best(t);
+		rows.push([
+			t.to_string(),
+			d.to_string(),
+			race.wins(t, d).to_string(),
+			first,
+			last,
+			best.to_string(),
+			race.dist(t, best).to_string(),
+		]);
+	}
+
+	let mut widths = [0; 7];
+	for row in &rows {
+		for (w, s) in widths.iter_mut().zip(row) {
+			*w = (*w).max(s.len());
+		}
+	}
+
+	let mut buf = String::with_capacity(rows.len() * (widths.iter().sum::<usize>() + 2 * widths.len()));
+	for row in rows {
+		for (i, (w, s)) in widths.iter().zip(&row).enumerate() {
+			let sep = if i == 0 { "" } else { "  " };
+			_ = write!(&mut buf, "{sep}{s:>w$}");
+		}
+		_ = writeln!(&mut buf);
+	}
+	buf
+}
+
+/// Finds the first `x` in `lo..hi` where `f` is false, `hi` if none.
+fn partition_point(mut lo: u64, mut hi: u64, f: impl Fn(u64) -> bool) -> u64 {
+	while lo < hi {
+		let mid = lo + (hi - lo) / 2;
+		if f(mid) {
+			lo = mid + 1;
+		} else {
+			hi = mid;
+		}
+	}
+	lo
+}
+
+/// Gives the first hold time with `h * (t - h) > d`, the last one is `t - h`.
+///
+/// The closed form root is only an estimate, as `isqrt` rounds down, and
+/// gets corrected to the exact boundary. Everything fits into `u128`.
+fn window(t: u64, d: u64) -> Option<u64> {
+	let (t, d) = (t as u128, d as u128);
+	let dist = |h: u128| h * (t - h);
+
+	let half = t / 2;
+	if dist(half) <= d {
+		return None;
+	}
+
+	let disc = t * t - 4 * d;
+	let mut lo = ((t - disc.isqrt()) / 2).min(half);
+	while lo > 0 && dist(lo - 1) > d {
+		lo -= 1;
+	}
+	while dist(lo) <= d {
+		lo += 1;
+	}
+
+	Some(lo as u64)
 }
 
 #[cfg(test)]
@@ -53,11 +216,116 @@ Distance:  9  40  200"#;
 
 	#[test]
 	fn test_part1() {
-		assert_eq!(part1(INPUT), 288);
+		assert_eq!(part1(&parse(INPUT, false).unwrap(), &RACE), 288);
 	}
 
 	#[test]
 	fn test_part2() {
-		assert_eq!(part2(INPUT), 71503);
+		assert_eq!(part2(&parse(INPUT, true).unwrap(), &RACE), 71503);
+	}
+
+	fn solve(t: u64, d: u64) -> u64 {
+		RACE.wins(t, d)
+	}
+
+	fn brute(t: u64, d: u64) -> u64 {
+		(0..=t).filter(|&h| h as u128 * (t - h) as u128 > d as u128).count() as u64
+	}
+
+	#[test]
+	fn test_solve() {
+		for t in 0..40 {
+			for d in 0..t * t / 4 + 2 {
+				assert_eq!(solve(t, d), brute(t, d), "t = {t}, d = {d}");
+			}
+		}
+	}
+
+	#[test]
+	fn test_precision() {
+		// Around 2^53 floats can't tell neighbouring distances apart.
+		for t in [(1 << 27) + 1, 1 << 32, (1 << 33) - 1, (1 << 40) + 7, u64::MAX / 3] {
+			for k in [1, 2, 1000, t / 3, t / 2 - 1] {
+				let Some(d) = k.checked_mul(t - k) else { continue };
+				assert_eq!(solve(t, d),     t - 2 * k - 1, "t = {t}, k = {k}");
+				assert_eq!(solve(t, d - 1), t - 2 * k + 1, "t = {t}, k = {k}");
+			}
+		}
+	}
+
+	#[test]
+	fn test_extremes() {
+		let t = u64::MAX;
+		assert_eq!(solve(t, u64::MAX), t - 3);
+		assert_eq!(solve(t, 0), t - 1);
+		assert_eq!(solve(0, 0), 0);
+		assert_eq!(solve(2, 1), 0);
+		assert_eq!(solve(2, 0), 1);
+	}
+
+	#[test]
+	fn test_race() {
+		let races = [
+			Race { rate: 3, ..RACE },
+			Race { cap: Some(4), ..RACE },
+			Race { rate: 2, cap: Some(7), ..RACE },
+			Race { decel: 1, ..RACE },
+			Race { rate: 3, cap: Some(10), decel: 2 },
+		];
+		for race in races {
+			for t in 0..30 {
+				for d in 0..150 {
+					let brute = (0..=t).filter(|&h| race.dist(t, h) > d as u128).count() as u64;
+					assert_eq!(race.wins(t, d), brute, "{race:?}, t = {t}, d = {d}");
+				}
+			}
+		}
+
+		// Capped at 4 mm/ms, beating 90 mm in 30 ms takes holding 4..=7 ms.
+		assert_eq!(Race { cap: Some(4), ..RACE }.window(30, 90), Some((4, 7)));
+		// Speed 5 with decel 1 runs 5 + 4 + 3 + 2 + 1 mm at most.
+		assert_eq!(Race { decel: 1, ..RACE }.dist(100, 5), 15);
+		assert_eq!(Race { rate: 3, ..RACE }.wins(u64::MAX, u64::MAX), u64::MAX - 1);
+		assert_eq!(Race { rate: 0, ..RACE }.wins(10, 0), 0);
+	}
+
+	#[test]
+	fn test_saturation() {
+		// `5 * h * (t - h)` is way past `u128` around the middle.
+		let race = Race { rate: 5, ..RACE };
+		let t = u64::MAX;
+		assert_eq!(race.dist(t, t / 2), u128::MAX);
+		assert_eq!(race.wins(t, u64::MAX), t - 1);
+		assert!(race.dist(t, race.best(t)) == u128::MAX);
+		assert!(generate_table(&[(t, u64::MAX)], &race).contains(&(t - 1).to_string()));
+
+		// Anything but not holding or not moving beats the record.
+		let race = Race { rate: u64::MAX, decel: 1, ..RACE };
+		assert_eq!(race.dist(t, t / 2), u128::MAX);
+		assert_eq!(race.wins(t, u64::MAX), t - 1);
+		assert!(generate_table(&[(t, u64::MAX)], &race).contains(&(t - 1).to_string()));
+	}
+
+	#[test]
+	fn test_parse() {
+		assert_eq!(parse(INPUT, false), Some(vec![(7, 9), (15, 40), (30, 200)]));
+		assert_eq!(parse(INPUT, true), Some(vec![(71530, 940200)]));
+		assert_eq!(parse("Time: 7 15\nDistance: 9", false), None);
+		assert_eq!(parse("Time: 7 x\nDistance: 9 40", false), None);
+		assert_eq!(parse("7\n9", false), None);
+	}
+
+	#[test]
+	fn test_table() {
+		let races = parse(INPUT, false).unwrap();
+		assert_eq!(RACE.best(7), 3);
+		assert_eq!(RACE.window(30, 200), Some((11, 19)));
+		assert_eq!(generate_table(&races, &RACE), [
+			"time  record  ways  first  last  best  best dist\n",
+			"   7       9     4      2     5     3         12\n",
+			"  15      40     8      4    11     7         56\n",
+			"  30     200     9     11    19    15        225\n",
+		].concat());
+		assert!(generate_table(&[(4, 4)], &RACE).ends_with("   4       4     0      -     -     2          4\n"));
 	}
 }
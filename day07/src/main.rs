@@ -1,3 +1,6 @@
+use std::env;
+use std::cmp::Reverse;
+use std::fmt::Write;
 use std::io::{self, Read};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -7,64 +10,253 @@ fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let p1 = solve::<false>(&input);
+	let mut args = env::args().skip(1).collect::<Vec<_>>();
+	let table = args.iter().position(|a| a == "table").map(|i| args.remove(i)).is_some();
+
+	if !args.is_empty() {
+		let mut rules = Rules::part1();
+		for arg in args {
+			let (k, v) = arg.split_once('=').ok_or_else(|| format!("Expected <param>=<value>, got '{arg}'"))?;
+			match k {
+				"order"      => rules.order = v.bytes().collect(),
+				"wild"       => rules.wild  = v.bytes().collect(),
+				"size"       => rules.size  = v.parse()?,
+				"categories" => rules.categories = parse_categories(v)?,
+				_            => return Err(format!("Unknown rule '{k}'").into()),
+			}
+		}
+		if table {
+			print!("{}", generate_table(&ranked(&input, &rules)?, &rules));
+		}
+		let p = solve(&input, &rules)?;
+		println!("p = {p}");
+		return Ok(());
+	}
+
+	if table {
+		for rules in [Rules::part1(), Rules::part2()] {
+			print!("{}", generate_table(&ranked(&input, &rules)?, &rules));
+		}
+	}
+
+	let p1 = solve(&input, &Rules::part1())?;
 	println!("p1 = {p1}");
-	let p2 = solve::<true>(&input);
+	let p2 = solve(&input, &Rules::part2())?;
 	println!("p2 = {p2}");
 
 	Ok(())
 }
 
-fn solve<const P2: bool>(input: &str) -> i64 {
-	let mut top: Vec<_> = input
-		.lines()
-		.filter_map(|s| {
-			let (hand, bid) = s.split_once(' ')?;
-			let mut h = [0; 5];
-			for (i, b) in hand.bytes().enumerate() {
-				h[i] = card_value::<P2>(b) as u8;
+/// Hand category as the groups of same cards it needs, like `[3, 2]` for
+/// a full house. Any stronger hand has those too.
+#[derive(Debug, Clone, PartialEq)]
+struct Category {
+	name:   String,
+	groups: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Rules {
+	/// Cards from the weakest to the strongest.
+	order:      Vec<u8>,
+	/// Cards which could stand in for any other one.
+	wild:       Vec<u8>,
+	size:       usize,
+	/// Categories from the strongest to the weakest.
+	categories: Vec<Category>,
+}
+
+impl Rules {
+	fn part1() -> Self {
+		Rules {
+			order:      b"23456789TJQKA".to_vec(),
+			wild:       Vec::new(),
+			size:       5,
+			categories: parse_categories("five:5,four:4,full-house:3+2,three:3,two-pair:2+2,pair:2,high-card:1").unwrap(),
+		}
+	}
+
+	fn part2() -> Self {
+		Rules {
+			order: b"J23456789TQKA".to_vec(),
+			wild:  b"J".to_vec(),
+			..Rules::part1()
+		}
+	}
+
+	fn value(&self, b: u8) -> usize {
+		self.order.iter().position(|&c| c == b).unwrap_or(0)
+	}
+
+	/// Gives the index of the strongest category a hand fits into, and the
+	/// hand with wildcards replaced by the cards getting it there.
+	fn classify(&self, hand: &[u8]) -> (Option<usize>, Vec<u8>) {
+		let mut kinds: Vec<(usize, u8)> = Vec::new();
+		let mut wild = 0;
+		for &b in hand {
+			if self.wild.contains(&b) {
+				wild += 1;
+				continue;
 			}
-			let r = rank::<P2>(hand);
-			let b = bid.parse::<i64>().ok()?;
+			match kinds.iter_mut().find(|(_, c)| *c == b) {
+				Some((k, _)) => *k += 1,
+				None         => kinds.push((1, b)),
+			}
+		}
+		kinds.sort_unstable_by_key(|&(k, c)| Reverse((k, self.value(c))));
 
-			Some((hand, h, r, b))
-		})
-		.collect();
+		// Wildcards fill in what is missing, pairing the biggest groups
+		// with the biggest requirements misses the least.
+		let category = self
+			.categories
+			.iter()
+			.position(|c| {
+				let missing = c
+					.groups
+					.iter()
+					.enumerate()
+					.map(|(i, &g)| g.saturating_sub(kinds.get(i).map_or(0, |&(k, _)| k)))
+					.sum::<usize>();
+				missing <= wild
+			});
 
-	top.sort_by_cached_key(|&(_, h, r, _)| (r, h));
+		// Missing groups are started with the strongest unused cards.
+		let mut spare = self
+			.order
+			.iter()
+			.rev()
+			.copied()
+			.filter(|c| !self.wild.contains(c) && kinds.iter().all(|&(_, k)| k != *c));
 
-	top
-		.into_iter()
-		.enumerate()
-		.map(|(i, x)| (i as i64 + 1) * x.3)
-		.sum()
+		let mut fills = Vec::with_capacity(wild);
+		for (i, &g) in category.map_or(&[][..], |i| &self.categories[i].groups).iter().enumerate() {
+			let Some(c) = kinds.get(i).map(|&(_, c)| c).or_else(|| spare.next()) else { break };
+			let k = kinds.get(i).map_or(0, |&(k, _)| k);
+			fills.extend(std::iter::repeat_n(c, g.saturating_sub(k)));
+		}
+		// The rest just joins the biggest group.
+		if let Some(c) = kinds.first().map(|&(_, c)| c).or(fills.first().copied()).or_else(|| spare.next()) {
+			fills.resize(wild, c);
+		}
+
+		let mut fills = fills.into_iter();
+		let hand = hand
+			.iter()
+			.map(|&b| if self.wild.contains(&b) { fills.next().unwrap_or(b) } else { b })
+			.collect();
+
+		(category, hand)
+	}
+
+	fn strength(&self, category: Option<usize>, hand: &[u8]) -> (usize, Vec<usize>) {
+		let category = category.map_or(0, |i| self.categories.len() - i);
+		let values   = hand.iter().map(|&b| self.value(b)).collect();
+		(category, values)
+	}
+}
+
+/// Parses categories like `five:5,full-house:3+2`.
+fn parse_categories(s: &str) -> Result<Vec<Category>> {
+	s
+		.split(',')
+		.map(|c| {
+			let (name, groups) = c.split_once(':').ok_or_else(|| format!("Bad category '{c}'"))?;
+			let mut groups = groups
+				.split('+')
+				.map(|g| g.parse())
+				.collect::<Result<Vec<usize>, _>>()?;
+			// Matching pairs the biggest groups first, whatever order they're written in.
+			groups.sort_unstable_by_key(|&g| Reverse(g));
+			Ok(Category { name: name.to_string(), groups })
+		})
+		.collect()
 }
 
-fn rank<const P2: bool>(hand: &str) -> [u8; 2] {
-	let mut table = [0; 13];
+struct Row<'a> {
+	hand:     &'a str,
+	bid:      i64,
+	category: Option<usize>,
+	/// Hand with wildcards substituted.
+	best:     String,
+}
+
+/// Reads `(hand, bid)` lines, reporting every bad one.
+fn parse<'a>(input: &'a str, rules: &Rules) -> Result<Vec<(&'a str, i64)>> {
+	let mut hands  = Vec::new();
+	let mut errors = Vec::new();
+
+	for (i, s) in input.lines().enumerate() {
+		let line = i + 1;
+		if s.trim().is_empty() {
+			continue;
+		}
+
+		let Some((hand, bid)) = s.split_once(' ') else {
+			errors.push(format!("line {line}: expected '<hand> <bid>', got '{s}'"));
+			continue;
+		};
+		let (hand, bid) = (hand.trim(), bid.trim());
 
-	let mut js = 0;
-	for b in hand.bytes() {
-		if P2 && b == b'J' {
-			js += 1;
-		} else {
-			table[card_value::<P2>(b)] += 1;
+		let known = |c: char| c.is_ascii() && (rules.order.contains(&(c as u8)) || rules.wild.contains(&(c as u8)));
+		if hand.chars().count() != rules.size {
+			errors.push(format!("line {line}: hand '{hand}' has {} cards instead of {}", hand.chars().count(), rules.size));
+		}
+		if let Some(c) = hand.chars().find(|&c| !known(c)) {
+			errors.push(format!("line {line}: hand '{hand}' has an unknown card '{c}'"));
+		}
+		if let Some(prev) = hands.iter().position(|&(h, _, _)| h == hand) {
+			errors.push(format!("line {line}: hand '{hand}' is already dealt at line {}", hands[prev].2));
+		}
+		match bid.parse::<i64>() {
+			Ok(bid) => hands.push((hand, bid, line)),
+			Err(_)  => errors.push(format!("line {line}: bid '{bid}' is not a number")),
 		}
 	}
 
-	table.sort_unstable();
-	let mut rank: [u8; 2] = table[11..].try_into().unwrap();
-	rank.reverse();
-	rank[0] += js;
-	rank
+	if !errors.is_empty() {
+		return Err(errors.join("\n").into());
+	}
+
+	Ok(hands.into_iter().map(|(hand, bid, _)| (hand, bid)).collect())
 }
 
-fn card_value<const P2: bool>(b: u8) -> usize {
-	let cards = if !P2 { b"23456789TJQKA" } else { b"J23456789TQKA" };
-	cards
-		.iter()
-		.position(|&c| c == b)
-		.unwrap()
+/// Gives hands from the weakest to the strongest.
+fn ranked<'a>(input: &'a str, rules: &Rules) -> Result<Vec<Row<'a>>> {
+	let mut top = parse(input, rules)?
+		.into_iter()
+		.map(|(hand, bid)| {
+			let (category, best) = rules.classify(hand.as_bytes());
+			let best = String::from_utf8_lossy(&best).into_owned();
+			Row { hand, bid, category, best }
+		})
+		.collect::<Vec<_>>();
+
+	top.sort_by_cached_key(|r| rules.strength(r.category, r.hand.as_bytes()));
+	Ok(top)
+}
+
+fn solve(input: &str, rules: &Rules) -> Result<i64> {
+	let winnings = ranked(input, rules)?
+		.into_iter()
+		.enumerate()
+		.map(|(i, r)| (i as i64 + 1) * r.bid)
+		.sum();
+	Ok(winnings)
+}
+
+fn generate_table(rows: &[Row], rules: &Rules) -> String {
+	let name = |r: &Row| r.category.map_or("-", |i| rules.categories[i].name.as_str());
+	let size = rules.size.max(4);
+	let kind = rows.iter().map(|r| name(r).len()).max().unwrap_or(0).max(4);
+	let bid  = rows.iter().map(|r| r.bid.to_string().len()).max().unwrap_or(0).max(3);
+
+	let mut buf = String::with_capacity(rows.len() * 48);
+	_ = writeln!(&mut buf, "{:>5}  {:size$}  {:size$}  {:kind$}  {:>bid$}  winnings", "rank", "hand", "as", "type", "bid");
+	for (i, r) in rows.iter().enumerate().rev() {
+		let rank = i + 1;
+		_ = writeln!(&mut buf, "{rank:>5}  {:size$}  {:size$}  {:kind$}  {:>bid$}  {:>8}", r.hand, r.best, name(r), r.bid, rank as i64 * r.bid);
+	}
+	buf
 }
 
 #[cfg(test)]
@@ -79,11 +271,114 @@ QQQJA 483"#;
 
 	#[test]
 	fn test_part1() {
-		assert_eq!(solve::<false>(INPUT), 6440);
+		assert_eq!(solve(INPUT, &Rules::part1()).unwrap(), 6440);
 	}
 
 	#[test]
 	fn test_part2() {
-		assert_eq!(solve::<true>(INPUT), 5905);
+		assert_eq!(solve(INPUT, &Rules::part2()).unwrap(), 5905);
+	}
+
+	#[test]
+	fn test_classify() {
+		let rules = Rules::part2();
+		let name = |hand: &str| &rules.categories[rules.classify(hand.as_bytes()).0.unwrap()].name;
+		assert_eq!(name("JJJJJ"), "five");
+		assert_eq!(name("KTJJT"), "four");
+		assert_eq!(name("2233J"), "full-house");
+		assert_eq!(name("2345J"), "pair");
+		assert_eq!(name("23456"), "high-card");
+
+		let rules = Rules { wild: b"J2".to_vec(), ..Rules::part2() };
+		let name = |hand: &str| &rules.categories[rules.classify(hand.as_bytes()).0.unwrap()].name;
+		assert_eq!(name("J2345"), "three");
+		assert_eq!(name("J2335"), "four");
+	}
+
+	#[test]
+	fn test_rules() {
+		const INPUT: &str = r#"AAKQ 1
+KKKQ 10
+2345 100
+QQJJ 1000"#;
+		let rules = Rules {
+			size:       4,
+			categories: parse_categories("four:4,three:3,two-pair:2+2,pair:2,high-card:1").unwrap(),
+			..Rules::part1()
+		};
+		assert_eq!(solve(INPUT, &rules).unwrap(), 100 + 2 + 3 * 1000 + 4 * 10);
+
+		let rules = Rules { wild: b"J".to_vec(), order: b"J23456789TQKA".to_vec(), ..rules };
+		assert_eq!(solve(INPUT, &rules).unwrap(), 100 + 2 + 3 * 10 + 4 * 1000);
+
+		// Only pairs count, everything else is just a high card.
+		let rules = Rules { categories: parse_categories("pair:2,nothing:1").unwrap(), ..rules };
+		assert_eq!(solve(INPUT, &rules).unwrap(), 100 + 2 * 1000 + 3 * 10 + 4);
+
+		assert!(parse_categories("five:5,four").is_err());
+		assert!(parse_categories("five:x").is_err());
+
+		// Groups could be listed in any order.
+		let rules = Rules { categories: parse_categories("fh:2+3,three:3,pair:2,high:1").unwrap(), ..Rules::part1() };
+		assert_eq!(rules.categories[0].groups, [3, 2]);
+		assert_eq!(rules.classify(b"22333").0, Some(0));
+		assert_eq!(rules.classify(b"22334").0, Some(2));
+	}
+
+	#[test]
+	fn test_substitution() {
+		fn best(rules: &Rules, hand: &str) -> String {
+			String::from_utf8(rules.classify(hand.as_bytes()).1).unwrap()
+		}
+
+		let rules = Rules::part2();
+		assert_eq!(best(&rules, "KTJJT"), "KTTTT");
+		assert_eq!(best(&rules, "2233J"), "22333");
+		assert_eq!(best(&rules, "2345J"), "23455");
+		assert_eq!(best(&rules, "JJJJJ"), "AAAAA");
+		assert_eq!(best(&rules, "JJ23J"), "33233");
+		assert_eq!(best(&rules, "23456"), "23456");
+
+		let rules = Rules { categories: parse_categories("full-house:3+2,pair:2").unwrap(), ..rules };
+		// There is no five of a kind, so it is a full house with aces.
+		assert_eq!(best(&rules, "JJJ2J"), "22A2A");
+		assert_eq!(best(&rules, "JJJ45"), "55445");
+	}
+
+	#[test]
+	fn test_table() {
+		let rules = Rules::part2();
+		let table = generate_table(&ranked(INPUT, &rules).unwrap(), &rules);
+		assert_eq!(table, [
+			" rank  hand   as     type      bid  winnings\n",
+			"    5  KTJJT  KTTTT  four      220      1100\n",
+			"    4  QQQJA  QQQQA  four      483      1932\n",
+			"    3  T55J5  T5555  four      684      2052\n",
+			"    2  KK677  KK677  two-pair   28        56\n",
+			"    1  32T3K  32T3K  pair      765       765\n",
+		].concat());
+	}
+
+	#[test]
+	fn test_validation() {
+		const INPUT: &str = r#"32T3K 765
+T55J 684
+KK6X7 28
+32T3K 220
+QQQJA 48a
+
+QQQJA"#;
+		let err = solve(INPUT, &Rules::part1()).unwrap_err().to_string();
+		assert_eq!(err.lines().collect::<Vec<_>>(), [
+			"line 2: hand 'T55J' has 4 cards instead of 5",
+			"line 3: hand 'KK6X7' has an unknown card 'X'",
+			"line 4: hand '32T3K' is already dealt at line 1",
+			"line 5: bid '48a' is not a number",
+			"line 7: expected '<hand> <bid>', got 'QQQJA'",
+		]);
+
+		assert!(solve("KKKKKK 1", &Rules::part1()).is_err());
+		assert!(solve("KKKKé 1", &Rules::part1()).is_err());
+		assert_eq!(solve("KKKK 1\n", &Rules { size: 4, ..Rules::part1() }).unwrap(), 1);
 	}
 }
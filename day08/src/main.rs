@@ -1,3 +1,6 @@
+use std::fs;
+use std::env;
+use std::fmt::{self, Write};
 use std::collections::HashMap;
 use std::io::{self, Read};
 
@@ -8,83 +11,497 @@ fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let (guide, network) = parse(&input);
-	let p1 = part1(guide, &network);
-	println!("p1 = {p1}");
-	let p2 = part2(guide, &network);
-	println!("p2 = {p2}");
+	let mut start  = "A".to_string();
+	let mut end    = "Z".to_string();
+	let mut dot    = false;
+	let mut report = false;
+	for arg in env::args().skip(1) {
+		match (arg.as_str(), arg.split_once('=')) {
+			("dot", _)              => dot = true,
+			("report", _)           => report = true,
+			(_, Some(("start", s))) => start = s.to_string(),
+			(_, Some(("end", s)))   => end   = s.to_string(),
+			_ => return Err(format!("Expected dot, report, start=<suffix> or end=<suffix>, got '{arg}'").into()),
+		}
+	}
+
+	let (guide, network) = parse(&input)?;
+
+	let starts = mark(&network, |n| n.ends_with(&start));
+	let ends   = mark(&network, |n| n.ends_with(&end));
+	if dot {
+		fs::write("graph.dot", generate_dot(&network, &starts, &ends))?;
+		println!("Saved a network visualization, use it like this:");
+		println!("dot -Tsvg graph.dot -o graph.svg");
+	}
+	if report {
+		let a = analyse(&network, &starts, &ends);
+		let names = |ids: &[usize]| ids.iter().map(|&i| network.names[i]).collect::<Vec<_>>().join(", ");
+		let trivial = a.sccs.iter().filter(|c| c.len() == 1).count();
+		println!("sccs = {} ({trivial} single nodes)", a.sccs.len());
+		for c in a.sccs.iter().filter(|c| c.len() > 1) {
+			println!("  {}: {}", c.len(), names(c));
+		}
+		for (s, es) in &a.reach {
+			println!("{} reaches [{}]", network.names[*s], names(es));
+		}
+		println!("unreachable = [{}]", names(&a.unreachable));
+	}
+
+	match part1(guide, &network) {
+		Some(p1) => println!("p1 = {p1}"),
+		None     => println!("p1 = AAA never gets to ZZZ"),
+	}
+	let (p2, period) = part2(guide, &network, |n| n.ends_with(&start), |n| n.ends_with(&end));
+	if let Err(e) = period {
+		eprintln!("note: {e}, so ghosts are synchronised with big integers");
+	}
+	match p2 {
+		Some(p2) => println!("p2 = {p2}"),
+		None     => println!("p2 = ghosts never synchronise"),
+	}
 
 	Ok(())
 }
 
-fn parse(input: &str) -> (&str, HashMap<u64, u64>) {
-	let (guide, network) = input.split_once("\n\n").unwrap();
+struct Network<'a> {
+	names: Vec<&'a str>,
+	/// Left and right neighbours per node.
+	links: Vec<(usize, usize)>,
+}
+
+impl Network<'_> {
+	fn id(&self, name: &str) -> Option<usize> {
+		self.names.iter().position(|&n| n == name)
+	}
+
+	#[inline(always)]
+	fn step(&self, at: usize, s: u8) -> usize {
+		let (l, r) = self.links[at];
+		if s == b'L' { l } else { r }
+	}
+}
+
+fn parse(input: &str) -> Result<(&str, Network<'_>)> {
+	let mut lines = input.lines();
 
-	let network = network
-		.lines()
+	let guide = lines.next().unwrap_or_default().trim();
+	if guide.is_empty() || guide.bytes().any(|b| b != b'L' && b != b'R') {
+		return Err(format!("Bad guide '{guide}', should be a non-empty sequence of L and R").into());
+	}
+
+	let nodes = lines
+		.filter(|s| !s.trim().is_empty())
 		.map(|s| {
-			let bytes = s.as_bytes();
-			let node  = encode(&bytes[0..3]);
-			let lr    = encode(&bytes[7..10]) | encode(&bytes[12..15]) << 32;
-			(node, lr)
+			let err = || format!("Bad node '{s}', expected <name> = (<left>, <right>)");
+			let (node, lr) = s.split_once('=').ok_or_else(err)?;
+			let (l, r)     = lr
+				.trim()
+				.strip_prefix('(')
+				.and_then(|s| s.strip_suffix(')'))
+				.and_then(|s| s.split_once(','))
+				.ok_or_else(err)?;
+			Ok((node.trim(), l.trim(), r.trim()))
 		})
-		.collect();
+		.collect::<Result<Vec<_>>>()?;
+
+	let ids = nodes
+		.iter()
+		.enumerate()
+		.map(|(i, &(n, _, _))| (n, i))
+		.collect::<HashMap<_, _>>();
+	if ids.len() != nodes.len() {
+		return Err("Some node is defined more than once".into());
+	}
+
+	let id = |n: &str| ids.get(n).copied().ok_or_else(|| format!("Unknown node '{n}'"));
+	let links = nodes
+		.iter()
+		.map(|&(_, l, r)| Ok((id(l)?, id(r)?)))
+		.collect::<Result<_, String>>()?;
+	let names = nodes.into_iter().map(|(n, _, _)| n).collect();
+
+	Ok((guide, Network { names, links }))
+}
+
+fn part1(guide: &str, network: &Network) -> Option<u64> {
+	let start = network.id("AAA")?;
+	let ghost = ghost(start, guide, network, &mark(network, |n| n == "ZZZ"));
+	ghost.ends.into_iter().find(|&t| t > 0)
+}
+
+/// Gives the first step all ghosts are at the ends, and their combined
+/// period, which is an error once it doesn't fit 64 bits.
+fn part2(guide: &str, network: &Network, is_start: impl Fn(&str) -> bool, is_end: impl Fn(&str) -> bool) -> (Option<Big>, Result<u64>) {
+	let ends   = mark(network, is_end);
+	let ghosts = network
+		.names
+		.iter()
+		.enumerate()
+		.filter(|&(_, n)| is_start(n))
+		.map(|(i, _)| ghost(i, guide, network, &ends))
+		.collect::<Vec<_>>();
+	(sync(&ghosts), period(&ghosts))
+}
 
-	(guide, network)
+fn period(ghosts: &[Ghost]) -> Result<u64> {
+	ghosts.iter().try_fold(1, |l, g| lcm(l, g.cycle))
 }
 
-#[inline(always)]
-fn encode(b: &[u8]) -> u64 {
-	((b[0] - b'0') as u64) << 12 | ((b[1] - b'0') as u64) << 6 | (b[2] - b'0') as u64
+/// Tells which nodes match a predicate.
+fn mark(network: &Network, f: impl Fn(&str) -> bool) -> Vec<bool> {
+	network.names.iter().map(|n| f(n)).collect()
 }
 
-fn part1(guide: &str, network: &HashMap<u64, u64>) -> i64 {
-	trace::<false>(encode(b"AAA"), guide, network)
+struct Analysis {
+	/// Strongly connected components, the biggest first.
+	sccs:        Vec<Vec<usize>>,
+	/// Ends reachable from every start node.
+	reach:       Vec<(usize, Vec<usize>)>,
+	unreachable: Vec<usize>,
 }
 
-fn part2(guide: &str, network: &HashMap<u64, u64>) -> i64 {
-	network
-		.keys()
-		.filter(|&&k| k & 0b111111 == (b'A' - b'0') as u64)
-		.map(|&n| trace::<true>(n, guide, network))
-		.reduce(lcm)
-		.unwrap_or_default()
+fn analyse(network: &Network, starts: &[bool], ends: &[bool]) -> Analysis {
+	let n = network.names.len();
+
+	let reachable = |from: usize| {
+		let mut seen  = vec![false; n];
+		let mut stack = vec![from];
+		seen[from] = true;
+		while let Some(at) = stack.pop() {
+			let (l, r) = network.links[at];
+			for next in [l, r] {
+				if !seen[next] {
+					seen[next] = true;
+					stack.push(next);
+				}
+			}
+		}
+		seen
+	};
+
+	let mut any   = vec![false; n];
+	let mut reach = Vec::new();
+	for s in (0..n).filter(|&i| starts[i]) {
+		let seen = reachable(s);
+		for (a, b) in any.iter_mut().zip(&seen) {
+			*a |= b;
+		}
+		reach.push((s, (0..n).filter(|&i| seen[i] && ends[i]).collect()));
+	}
+	let unreachable = (0..n).filter(|&i| !any[i]).collect();
+
+	let mut sccs = sccs(network);
+	sccs.sort_by_key(|c| std::cmp::Reverse(c.len()));
+
+	Analysis { sccs, reach, unreachable }
 }
 
-fn trace<const P2: bool>(start: u64, guide: &str, network: &HashMap<u64, u64>) -> i64 {
-	guide
-		.bytes()
-		.cycle()
-		.scan(start, |at, s| {
-			let next = network[at];
-			*at = if s == b'L' {
-				next & 0xFFFF_FFFF
-			} else {
-				next >> 32
-			};
-			Some(*at & 0b111111 == (b'Z' - b'0') as u64)
+/// Kosaraju, but without recursion.
+fn sccs(network: &Network) -> Vec<Vec<usize>> {
+	let n = network.names.len();
+
+	let mut order = Vec::with_capacity(n);
+	let mut seen  = vec![false; n];
+	for root in 0..n {
+		if seen[root] {
+			continue;
+		}
+		seen[root] = true;
+		let mut stack = vec![(root, 0)];
+		while let Some((at, i)) = stack.pop() {
+			let (l, r) = network.links[at];
+			match [l, r].get(i) {
+				Some(&next) => {
+					stack.push((at, i + 1));
+					if !seen[next] {
+						seen[next] = true;
+						stack.push((next, 0));
+					}
+				},
+				None => order.push(at),
+			}
+		}
+	}
+
+	let mut rev = vec![Vec::new(); n];
+	for (i, &(l, r)) in network.links.iter().enumerate() {
+		rev[l].push(i);
+		if r != l {
+			rev[r].push(i);
+		}
+	}
+
+	let mut sccs = Vec::new();
+	let mut seen = vec![false; n];
+	for &root in order.iter().rev() {
+		if seen[root] {
+			continue;
+		}
+		seen[root] = true;
+		let mut scc   = Vec::new();
+		let mut stack = vec![root];
+		while let Some(at) = stack.pop() {
+			scc.push(at);
+			for &next in &rev[at] {
+				if !seen[next] {
+					seen[next] = true;
+					stack.push(next);
+				}
+			}
+		}
+		scc.sort_unstable();
+		sccs.push(scc);
+	}
+	sccs
+}
+
+fn generate_dot(network: &Network, starts: &[bool], ends: &[bool]) -> String {
+	let mut buf = String::with_capacity(network.names.len() * 32);
+
+	_ = writeln!(&mut buf, "digraph {{");
+	for (i, name) in network.names.iter().enumerate() {
+		match (starts[i], ends[i]) {
+			(true, true) => _ = writeln!(&mut buf, "    {name:?} [style=filled fillcolor=gold]"),
+			(true, _)    => _ = writeln!(&mut buf, "    {name:?} [style=filled fillcolor=palegreen]"),
+			(_, true)    => _ = writeln!(&mut buf, "    {name:?} [style=filled fillcolor=lightcoral]"),
+			_            => {},
+		}
+	}
+	for (i, &(l, r)) in network.links.iter().enumerate() {
+		let name = network.names[i];
+		_ = writeln!(&mut buf, "    {name:?} -> {:?} [label=L]", network.names[l]);
+		_ = writeln!(&mut buf, "    {name:?} -> {:?} [label=R]", network.names[r]);
+	}
+	_ = writeln!(&mut buf, "}}");
+
+	buf
+}
+
+/// Ghost's walk as `tail` steps followed by a `cycle` steps long loop,
+/// and all steps of those it stands on an end node.
+#[derive(Debug, PartialEq)]
+struct Ghost {
+	tail:  u64,
+	cycle: u64,
+	ends:  Vec<u64>,
+}
+
+impl Ghost {
+	fn at_end(&self, t: u64) -> bool {
+		let t = if t < self.tail { t } else { self.tail + (t - self.tail) % self.cycle };
+		self.ends.binary_search(&t).is_ok()
+	}
+}
+
+/// Walks until the same node is reached at the same guide position.
+fn ghost(start: usize, guide: &str, network: &Network, is_end: &[bool]) -> Ghost {
+	let guide = guide.as_bytes();
+
+	let mut seen = HashMap::new();
+	let mut ends = Vec::new();
+	let mut at   = start;
+	for t in 0.. {
+		let i = t as usize % guide.len();
+		if let Some(&s) = seen.get(&(at, i)) {
+			return Ghost { tail: s, cycle: t - s, ends };
+		}
+		seen.insert((at, i), t);
+		if is_end[at] {
+			ends.push(t);
+		}
+		at = network.step(at, guide[i]);
+	}
+	unreachable!()
+}
+
+/// Finds the first step all ghosts are at the end nodes.
+fn sync(ghosts: &[Ghost]) -> Option<Big> {
+	// Until the slowest ghost loops, it could only be one of its tail ends.
+	let slowest = ghosts.iter().max_by_key(|g| g.tail)?;
+	if let Some(&t) = slowest
+		.ends
+		.iter()
+		.filter(|&&t| t < slowest.tail)
+		.find(|&&t| ghosts.iter().all(|g| g.at_end(t)))
+	{
+		return Some(Big::from(t as u128));
+	}
+
+	// Afterwards everyone loops and every end is a `t = e (mod cycle)`,
+	// trying every combination of those via CRT.
+	let mut residues = vec![(Big::from(0), Big::from(1))];
+	for g in ghosts {
+		let mut next = residues
+			.iter()
+			.flat_map(|(r, m)| g
+					  .ends
+					  .iter()
+					  .filter(|&&e| e >= g.tail)
+					  .filter_map(move |&e| crt(r, m, e % g.cycle, g.cycle))
+			)
+			.collect::<Vec<_>>();
+		next.sort_unstable();
+		next.dedup();
+		residues = next;
+	}
+
+	// Below the start the residue fits in 64 bits, and so does the distance.
+	let start = Big::from(slowest.tail as u128);
+	residues
+		.into_iter()
+		.map(|(r, m)| match (r.to_u64(), m.to_u64()) {
+			_ if r >= start    => r,
+			(Some(r), Some(m)) => Big::from(r as u128 + (slowest.tail - r).div_ceil(m) as u128 * m as u128),
+			_                  => r.add(&m),
 		})
-		.position(|n| n)
-		.unwrap() as i64
-		+ 1
+		.min()
+}
+
+/// Combines `x = a (mod m)` and `x = b (mod n)` into `x = c (mod lcm(m, n))`,
+/// where only the accumulated `a` and `m` could get past 64 bits.
+fn crt(a: &Big, m: &Big, b: u64, n: u64) -> Option<(Big, Big)> {
+	let mn = m.rem(n);
+	let g  = gcd(mn, n);
+	let diff = b as i128 - a.rem(n) as i128;
+	if diff % g as i128 != 0 {
+		return None;
+	}
+
+	// `m / g` is invertible modulo `n / g`, and `k` is the number of `m` steps.
+	let ng = n / g;
+	let (_, p, _) = ext_gcd((mn / g) as i128, ng as i128);
+	let k = (diff / g as i128).rem_euclid(ng as i128) as u128 * p.rem_euclid(ng as i128) as u128 % ng as u128;
+	Some((a.add(&m.mul(k as u64)), m.mul(ng)))
 }
 
-fn lcm(first: i64, second: i64) -> i64 {
-    first * second / gcd(first, second)
+/// Gives `(g, x, y)` with `a * x + b * y = g = gcd(a, b)`.
+fn ext_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+	if b == 0 {
+		(a, 1, 0)
+	} else {
+		let (g, x, y) = ext_gcd(b, a % b);
+		(g, y, x - a / b * y)
+	}
+}
+
+/// Divides first, so only a result that is too big itself fails.
+fn lcm(first: u64, second: u64) -> Result<u64> {
+	if first == 0 || second == 0 {
+		return Ok(0);
+	}
+	(first / gcd(first, second))
+		.checked_mul(second)
+		.ok_or_else(|| format!("lcm({first}, {second}) overflows u64").into())
+}
+
+fn gcd(mut first: u64, mut second: u64) -> u64 {
+	while second != 0 {
+		(first, second) = (second, first % second);
+	}
+	first
+}
+
+/// Unsigned integer of any size as little-endian 32-bit digits without
+/// leading zeros, with just the operations CRT needs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Big(Vec<u32>);
+
+impl Big {
+	fn normalized(mut digits: Vec<u32>) -> Self {
+		while digits.last() == Some(&0) {
+			digits.pop();
+		}
+		Big(digits)
+	}
+
+	fn to_u64(&self) -> Option<u64> {
+		match self.0[..] {
+			[]     => Some(0),
+			[a]    => Some(a as u64),
+			[a, b] => Some(a as u64 | (b as u64) << 32),
+			_      => None,
+		}
+	}
+
+	fn add(&self, other: &Big) -> Big {
+		let mut digits = Vec::with_capacity(self.0.len().max(other.0.len()) + 1);
+		let mut carry  = 0;
+		for i in 0..self.0.len().max(other.0.len()) {
+			let sum = *self.0.get(i).unwrap_or(&0) as u64 + *other.0.get(i).unwrap_or(&0) as u64 + carry;
+			digits.push(sum as u32);
+			carry = sum >> 32;
+		}
+		digits.push(carry as u32);
+		Big::normalized(digits)
+	}
+
+	fn mul(&self, n: u64) -> Big {
+		let mut digits = Vec::with_capacity(self.0.len() + 2);
+		let mut carry  = 0;
+		for &d in &self.0 {
+			let prod = d as u128 * n as u128 + carry;
+			digits.push(prod as u32);
+			carry = prod >> 32;
+		}
+		digits.extend([carry as u32, (carry >> 32) as u32]);
+		Big::normalized(digits)
+	}
+
+	fn divrem(&self, n: u64) -> (Big, u64) {
+		let mut digits = vec![0; self.0.len()];
+		let mut rem    = 0;
+		for (i, &d) in self.0.iter().enumerate().rev() {
+			let cur = (rem as u128) << 32 | d as u128;
+			digits[i] = (cur / n as u128) as u32;
+			rem = (cur % n as u128) as u64;
+		}
+		(Big::normalized(digits), rem)
+	}
+
+	fn rem(&self, n: u64) -> u64 {
+		self.divrem(n).1
+	}
+}
+
+impl From<u128> for Big {
+	fn from(n: u128) -> Self {
+		Big::normalized((0..4).map(|i| (n >> (32 * i)) as u32).collect())
+	}
 }
 
-fn gcd(first: i64, second: i64) -> i64 {
-    let mut max = first.max(second);
-    let mut min = first.min(second);
+impl Ord for Big {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		self.0.len().cmp(&other.0.len()).then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+	}
+}
+
+impl PartialOrd for Big {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
 
-    loop {
-        let x = max % min;
-        if x == 0 {
-            return min;
-        }
-        max = min;
-        min = x;
-    }
+impl fmt::Display for Big {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		// Peels 9 decimal digits at a time, so the chunks go lowest first.
+		let mut chunks = Vec::new();
+		let mut n      = self.clone();
+		loop {
+			let (q, r) = n.divrem(1_000_000_000);
+			chunks.push(r);
+			if q.0.is_empty() {
+				break;
+			}
+			n = q;
+		}
+		let mut chunks = chunks.iter().rev();
+		write!(f, "{}", chunks.next().unwrap())?;
+		for c in chunks {
+			write!(f, "{c:09}")?;
+		}
+		Ok(())
+	}
 }
 
 #[cfg(test)]
@@ -118,17 +535,150 @@ ZZZ = (ZZZ, ZZZ)"#;
 22Z = (22B, 22B)
 XXX = (XXX, XXX)"#;
 
+	fn is_start(n: &str) -> bool {
+		n.ends_with('A')
+	}
+
+	fn is_end(n: &str) -> bool {
+		n.ends_with('Z')
+	}
+
 	#[test]
 	fn test_part1() {
-		let (guide, network) = parse(INPUT1);
-		assert_eq!(part1(guide, &network), 2);
-		let (guide, network) = parse(INPUT2);
-		assert_eq!(part1(guide, &network), 6);
+		let (guide, network) = parse(INPUT1).unwrap();
+		assert_eq!(part1(guide, &network), Some(2));
+		let (guide, network) = parse(INPUT2).unwrap();
+		assert_eq!(part1(guide, &network), Some(6));
 	}
 
 	#[test]
 	fn test_part2() {
-		let (guide, network) = parse(INPUT3);
-		assert_eq!(part2(guide, &network), 6);
+		let (guide, network) = parse(INPUT3).unwrap();
+		assert_eq!(part2(guide, &network, is_start, is_end).0, Some(Big::from(6)));
+		assert_eq!(part2(guide, &network, is_start, is_end).1.unwrap(), 6);
+	}
+
+	#[test]
+	fn test_ghosts() {
+		// Ends at odd steps from 3 and at `1 (mod 3)`, so 7 but not LCM's 3.
+		const OFFSET: &str = r#"L
+
+11A = (11B, 11B)
+11B = (11C, 11C)
+11C = (11Z, 11Z)
+11Z = (11C, 11C)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)"#;
+		let (guide, network) = parse(OFFSET).unwrap();
+		let ends = mark(&network, is_end);
+		assert_eq!(ghost(network.id("11A").unwrap(), guide, &network, &ends), Ghost { tail: 2, cycle: 2, ends: vec![3] });
+		assert_eq!(ghost(network.id("22A").unwrap(), guide, &network, &ends), Ghost { tail: 1, cycle: 3, ends: vec![1] });
+		assert_eq!(part2(guide, &network, is_start, is_end).0, Some(Big::from(7)));
+
+		// Odd and even steps never meet.
+		const NEVER: &str = r#"L
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (11Z, 11Z)
+22A = (22B, 22B)
+22B = (22Z, 22Z)
+22Z = (22B, 22B)"#;
+		let (guide, network) = parse(NEVER).unwrap();
+		assert_eq!(part2(guide, &network, is_start, is_end).0, None);
+
+		// The first ghost ends only once, before looping.
+		const TAIL: &str = r#"L
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22Z, 22Z)"#;
+		let (guide, network) = parse(TAIL).unwrap();
+		assert_eq!(part2(guide, &network, is_start, is_end).0, Some(Big::from(1)));
+
+		// Guide position matters, `11Z` is an end only after an `R`.
+		const GUIDE: &str = r#"LR
+
+11A = (11B, 11B)
+11B = (11A, 11Z)
+11Z = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22Z, 22Z)"#;
+		let (guide, network) = parse(GUIDE).unwrap();
+		assert_eq!(part2(guide, &network, is_start, is_end).0, Some(Big::from(2)));
+	}
+
+	#[test]
+	fn test_names() {
+		const INPUT: &str = r#"LR
+
+start=(  middle ,nowhere)
+middle  =  (nowhere,finish)
+nowhere = (nowhere, nowhere)
+finish = (start, start)
+ghost1 = (finish, nowhere)
+"#;
+		let (guide, network) = parse(INPUT).unwrap();
+		assert_eq!(network.names, ["start", "middle", "nowhere", "finish", "ghost1"]);
+		assert_eq!(network.links[0], (1, 2));
+		assert_eq!(part2(guide, &network, |n| n == "start", |n| n == "finish").0, Some(Big::from(2)));
+		assert_eq!(part2(guide, &network, |n| n.starts_with("st") || n.starts_with("gh"), |n| n == "finish").0, None);
+		assert_eq!(part1(guide, &network), None);
+
+		assert!(parse("LR\n\nAAA = (BBB, CCC)").is_err());
+		assert!(parse("LX\n\nAAA = (AAA, AAA)").is_err());
+		assert!(parse("L\n\nAAA = AAA, AAA").is_err());
+		assert!(parse("L\n\nAAA = (AAA, AAA)\nAAA = (AAA, AAA)").is_err());
+	}
+
+	#[test]
+	fn test_analysis() {
+		let (_, network) = parse(INPUT3).unwrap();
+		let starts = mark(&network, is_start);
+		let ends   = mark(&network, is_end);
+		let a = analyse(&network, &starts, &ends);
+		let names = |ids: &[usize]| ids.iter().map(|&i| network.names[i]).collect::<Vec<_>>();
+
+		assert_eq!(a.sccs.iter().map(|c| c.len()).collect::<Vec<_>>(), [3, 2, 1, 1, 1]);
+		assert_eq!(names(&a.sccs[0]), ["22B", "22C", "22Z"]);
+		assert_eq!(names(&a.sccs[1]), ["11B", "11Z"]);
+		assert_eq!(a.reach.iter().map(|(s, es)| (network.names[*s], names(es))).collect::<Vec<_>>(), [
+			("11A", vec!["11Z"]),
+			("22A", vec!["22Z"]),
+		]);
+		assert!(a.unreachable.is_empty());
+
+		let starts = mark(&network, |n| n == "22A");
+		let a = analyse(&network, &starts, &ends);
+		assert_eq!(names(&a.unreachable), ["11A", "11B", "11Z"]);
+	}
+
+	#[test]
+	fn test_lcm() {
+		assert_eq!(gcd(0, 7), 7);
+		assert_eq!(gcd(7, 0), 7);
+		assert_eq!(lcm(0, 7).unwrap(), 0);
+		assert_eq!(lcm(4, 6).unwrap(), 12);
+		assert_eq!(lcm(1 << 63, 1 << 62).unwrap(), 1 << 63);
+		assert_eq!(lcm(u64::MAX, u64::MAX - 1).unwrap_err().to_string(), format!("lcm({}, {}) overflows u64", u64::MAX, u64::MAX - 1));
+	}
+
+	#[test]
+	fn test_big() {
+		// Every ghost ends a step before its prime cycle, so it's their product less one.
+		let ghosts = [4294967291, 4294967279, 4294967231].map(|p| Ghost { tail: 0, cycle: p, ends: vec![p - 1] });
+		let t = sync(&ghosts).unwrap();
+		assert_eq!(period(&ghosts).unwrap_err().to_string(), format!("lcm({}, 4294967231) overflows u64", 4294967291u64 * 4294967279));
+		assert_eq!(t.to_u64(), None);
+		assert_eq!(t.to_string(), "79228160909397609687688407658");
+
+		assert_eq!(Big::from(u64::MAX as u128).add(&Big::from(1)), Big::from(1 << 64));
+		assert_eq!(Big::from(1 << 64).mul(u64::MAX).rem(1_000_000_007), ((1u128 << 64) * u64::MAX as u128 % 1_000_000_007) as u64);
+		assert_eq!(Big::from(0).to_string(), "0");
+		assert_eq!(Big::from(1_000_000_000).to_string(), "1000000000");
 	}
 }
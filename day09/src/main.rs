@@ -1,3 +1,6 @@
+use std::env;
+use std::fmt::{self, Write};
+use std::cmp::Ordering;
 use std::io::{self, Read};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -7,31 +10,348 @@ fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let (p1, p2) = solve(&input);
-	println!("p1 = {p1}");
-	println!("p2 = {p2}");
+	let mut args = env::args().skip(1).collect::<Vec<_>>();
+	let degrees  = args.iter().position(|a| a == "degrees").map(|i| args.remove(i)).is_some();
+	let poly     = args.iter().position(|a| a == "poly").map(|i| args.remove(i)).is_some();
+
+	let histories = parse(&input)?;
+
+	for (i, h) in histories.iter().enumerate() {
+		let n = i + 1;
+		match h.degree() {
+			Some(d) if degrees => println!("history {n}: degree {d}"),
+			Some(_)            => {},
+			None               => eprintln!("warning: history {n} never gets constant differences, using its degree {} fit", h.len - 1),
+		}
+		if poly {
+			println!("history {n}: p(x) = {}", generate_polynomial(&h.polynomial()));
+		}
+	}
+
+	for arg in args {
+		let (k, v) = arg.split_once('=').ok_or_else(|| format!("Expected <direction>=<steps>, got '{arg}'"))?;
+		let n = v.parse::<i128>()?;
+		let sum = match k {
+			"next" => total(&histories, |h| h.next(n)),
+			"prev" => total(&histories, |h| h.prev(n)),
+			"at"   => total(&histories, |h| h.at(n)),
+			_      => return Err(format!("Expected next=, prev= or at=, got '{k}'").into()),
+		};
+		println!("{k} {n} = {sum}");
+	}
+
+	println!("p1 = {}", total(&histories, |h| h.next(1)));
+	println!("p2 = {}", total(&histories, |h| h.prev(1)));
 
 	Ok(())
 }
 
-fn solve(input: &str) -> (i32, i32) {
-	let (p1, p2) = input
-		.lines()
-		.map(|s| s.split_whitespace().filter_map(|s| s.parse().ok()).collect())
-		.map(|mut s: Vec<i32>| {
-			s.insert(0, 0);
-			s.push(0);
+/// History in the Newton form: the leading entries of every difference
+/// row, which give the value at any position `x` as `sum(d[j] * C(x, j))`.
+struct History {
+	len:   usize,
+	diffs: Vec<Int>,
+	/// Whether a row of zeros showed up, rather than running out of values.
+	exact: bool,
+}
+
+impl History {
+	/// Rows could double at every step, so those are big integers.
+	fn new(values: &[i64]) -> Self {
+		let mut row   = values.iter().map(|&v| Int::from(v as i128)).collect::<Vec<_>>();
+		let mut diffs = Vec::with_capacity(row.len());
+		while !row.is_empty() && row.iter().any(|v| !v.mag.is_empty()) {
+			diffs.push(row[0].clone());
+			row = row.windows(2).map(|w| w[1].sub(&w[0])).collect();
+		}
+		History { len: values.len(), diffs, exact: !row.is_empty() }
+	}
+
+	/// Degree at which differences become constant, if they ever do.
+	fn degree(&self) -> Option<usize> {
+		self.exact.then(|| self.diffs.len().saturating_sub(1))
+	}
+
+	/// Coefficients of the fitted polynomial in `x`, the lowest power first,
+	/// as reduced `(numerator, denominator)` fractions.
+	fn polynomial(&self) -> Vec<(Int, Int)> {
+		let d = self.diffs.len().saturating_sub(1);
+
+		// Everything is over `d!` until reduced, and `C(x, j)` is the falling
+		// factorial `x(x - 1)..(x - j + 1)` over `j!`.
+		let mut nums    = vec![Int::from(0); d + 1];
+		let mut falling = vec![Int::from(1)];
+		for (j, diff) in self.diffs.iter().enumerate() {
+			if j > 0 {
+				let k = Int::from(1 - j as i128);
+				let mut next = vec![Int::from(0); j + 1];
+				for (i, c) in falling.iter().enumerate() {
+					next[i + 1] = next[i + 1].add(c);
+					next[i]     = next[i].add(&c.mul(&k));
+				}
+				falling = next;
+			}
+			let scale = (j + 1..=d).fold(diff.clone(), |s, f| s.mul(&Int::from(f as i128)));
+			for (n, c) in nums.iter_mut().zip(&falling) {
+				*n = n.add(&c.mul(&scale));
+			}
+		}
+
+		// Only primes up to `d` could divide `d!`.
+		let den    = (1..=d).fold(Int::from(1), |s, f| s.mul(&Int::from(f as i128)));
+		let primes = (2..=d as u32).filter(|&p| (2..p).all(|q| p % q != 0)).collect::<Vec<_>>();
+		nums
+			.into_iter()
+			.map(|mut n| {
+				let mut den = den.clone();
+				for &p in &primes {
+					while n.divrem(p).1 == 0 && den.divrem(p).1 == 0 {
+						n   = n.div(p);
+						den = den.div(p);
+					}
+				}
+				(n, den)
+			})
+			.collect()
+	}
+
+	/// Value `k` steps after the last one.
+	fn next(&self, k: i128) -> Int {
+		self.at(self.len as i128 - 1 + k)
+	}
+
+	/// Value `k` steps before the first one.
+	fn prev(&self, k: i128) -> Int {
+		self.at(-k)
+	}
+
+	/// Value at position `x`, where the history is at `0..len`.
+	fn at(&self, x: i128) -> Int {
+		self.at_small(x).map(Int::from).unwrap_or_else(|| self.at_big(x))
+	}
 
-			let mut n = s.len();
-			while n > 2 {
-				for i in 1..n { s[i - 1] = s[i] - s[i - 1]; }
-				n -= 1;
+	/// Only when every diff and all the steps fit in `i128`.
+	fn at_small(&self, x: i128) -> Option<i128> {
+		let mut sum = 0i128;
+		let mut c   = 1i128;
+		for (j, d) in self.diffs.iter().enumerate() {
+			let d = d.to_i128()?;
+			if j > 0 {
+				c = c.checked_mul(x.checked_sub(j as i128 - 1)?)? / j as i128;
 			}
+			sum = sum.checked_add(c.checked_mul(d)?)?;
+		}
+		Some(sum)
+	}
+
+	fn at_big(&self, x: i128) -> Int {
+		let mut sum = Int::from(0);
+		let mut c   = Int::from(1);
+		for (j, d) in self.diffs.iter().enumerate() {
+			if j > 0 {
+				// `C(x, j) = C(x, j - 1) * (x - j + 1) / j` divides exactly.
+				c = c.mul(&Int::from(x).add(&Int::from(1 - j as i128))).div(j as u32);
+			}
+			sum = sum.add(&c.mul(d));
+		}
+		sum
+	}
+}
+
+/// Reads a history per line, reporting every bad number.
+fn parse(input: &str) -> Result<Vec<History>> {
+	let mut histories = Vec::new();
+	let mut errors    = Vec::new();
+
+	for (i, s) in input.lines().enumerate() {
+		let line = i + 1;
+		if s.trim().is_empty() {
+			continue;
+		}
+
+		let mut values = Vec::new();
+		for v in s.split_whitespace() {
+			match v.parse::<i64>() {
+				Ok(v)  => values.push(v),
+				Err(_) => errors.push(format!("line {line}: '{v}' is not a number")),
+			}
+		}
+		histories.push(History::new(&values));
+	}
+
+	if !errors.is_empty() {
+		return Err(errors.join("\n").into());
+	}
+
+	Ok(histories)
+}
+
+fn generate_polynomial(coeffs: &[(Int, Int)]) -> String {
+	let one = Int::from(1);
+
+	let mut buf = String::new();
+	for (i, (n, d)) in coeffs.iter().enumerate().rev() {
+		if n.mag.is_empty() {
+			continue;
+		}
+		let sign = match (buf.is_empty(), n.neg) {
+			(true, true)   => "-",
+			(true, false)  => "",
+			(false, true)  => " - ",
+			(false, false) => " + ",
+		};
+		let abs  = Int::new(false, n.mag.clone());
+		let coef = match (abs == one && i > 0, *d == one) {
+			(true, true)   => String::new(),
+			(_, true)      => abs.to_string(),
+			(_, false)     => format!("({abs}/{d})"),
+		};
+		match i {
+			0 => _ = write!(&mut buf, "{sign}{coef}"),
+			1 => _ = write!(&mut buf, "{sign}{coef}x"),
+			_ => _ = write!(&mut buf, "{sign}{coef}x^{i}"),
+		}
+	}
+	if buf.is_empty() {
+		buf.push('0');
+	}
+
+	buf
+}
+
+fn total(histories: &[History], f: impl Fn(&History) -> Int) -> Int {
+	histories.iter().fold(Int::from(0), |sum, h| sum.add(&f(h)))
+}
 
-			(-s[1], -s[0])
-		})
-		.fold((0, 0), |(s1, s2), (p1, p2)| (s1 + p1, s2 + p2));
-	(p1, p2.abs())
+/// Signed integer of any size as a sign and little-endian 32-bit digits
+/// without leading zeros, so zero is never negative.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Int {
+	neg: bool,
+	mag: Vec<u32>,
+}
+
+impl Int {
+	fn new(neg: bool, mut mag: Vec<u32>) -> Self {
+		while mag.last() == Some(&0) {
+			mag.pop();
+		}
+		Int { neg: neg && !mag.is_empty(), mag }
+	}
+
+	fn add(&self, other: &Int) -> Int {
+		if self.neg == other.neg {
+			return Int::new(self.neg, add_mag(&self.mag, &other.mag));
+		}
+		match cmp_mag(&self.mag, &other.mag) {
+			Ordering::Less => Int::new(other.neg, sub_mag(&other.mag, &self.mag)),
+			_              => Int::new(self.neg, sub_mag(&self.mag, &other.mag)),
+		}
+	}
+
+	fn sub(&self, other: &Int) -> Int {
+		self.add(&Int::new(!other.neg, other.mag.clone()))
+	}
+
+	fn mul(&self, other: &Int) -> Int {
+		let mut mag = vec![0u32; self.mag.len() + other.mag.len()];
+		for (i, &a) in self.mag.iter().enumerate() {
+			let mut carry = 0u64;
+			for (j, &b) in other.mag.iter().enumerate() {
+				let cur = mag[i + j] as u64 + a as u64 * b as u64 + carry;
+				mag[i + j] = cur as u32;
+				carry = cur >> 32;
+			}
+			mag[i + other.mag.len()] = carry as u32;
+		}
+		Int::new(self.neg != other.neg, mag)
+	}
+
+	fn to_i128(&self) -> Option<i128> {
+		if self.mag.len() > 4 {
+			return None;
+		}
+		let abs = self.mag.iter().rev().fold(0u128, |n, &d| n << 32 | d as u128);
+		if self.neg { 0i128.checked_sub_unsigned(abs) } else { i128::try_from(abs).ok() }
+	}
+
+	/// Truncating division, which is all that's needed for exact ones.
+	fn div(&self, n: u32) -> Int {
+		self.divrem(n).0
+	}
+
+	fn divrem(&self, n: u32) -> (Int, u32) {
+		let mut mag = vec![0; self.mag.len()];
+		let mut rem = 0u64;
+		for (i, &d) in self.mag.iter().enumerate().rev() {
+			let cur = rem << 32 | d as u64;
+			mag[i] = (cur / n as u64) as u32;
+			rem = cur % n as u64;
+		}
+		(Int::new(self.neg, mag), rem as u32)
+	}
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+	a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut mag   = Vec::with_capacity(a.len().max(b.len()) + 1);
+	let mut carry = 0u64;
+	for i in 0..a.len().max(b.len()) {
+		let cur = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+		mag.push(cur as u32);
+		carry = cur >> 32;
+	}
+	mag.push(carry as u32);
+	mag
+}
+
+/// Expects `a >= b`.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut mag    = Vec::with_capacity(a.len());
+	let mut borrow = 0i64;
+	for (i, &d) in a.iter().enumerate() {
+		let mut cur = d as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+		borrow = (cur < 0) as i64;
+		cur += borrow << 32;
+		mag.push(cur as u32);
+	}
+	mag
+}
+
+impl From<i128> for Int {
+	fn from(n: i128) -> Self {
+		let abs = n.unsigned_abs();
+		Int::new(n < 0, (0..4).map(|i| (abs >> (32 * i)) as u32).collect())
+	}
+}
+
+impl fmt::Display for Int {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		// The magnitude goes out in base 10^9 chunks, lowest first, and the
+		// sign is put in front of it.
+		let mut chunks = Vec::new();
+		let mut n      = Int::new(false, self.mag.clone());
+		loop {
+			let (q, r) = n.divrem(1_000_000_000);
+			chunks.push(r);
+			if q.mag.is_empty() {
+				break;
+			}
+			n = q;
+		}
+		if self.neg {
+			write!(f, "-")?;
+		}
+		let mut chunks = chunks.iter().rev();
+		write!(f, "{}", chunks.next().unwrap())?;
+		for c in chunks {
+			write!(f, "{c:09}")?;
+		}
+		Ok(())
+	}
 }
 
 #[cfg(test)]
@@ -44,11 +364,70 @@ mod tests {
 
 	#[test]
 	fn test_part1() {
-		assert_eq!(solve(INPUT).0, 114);
+		let histories = parse(INPUT).unwrap();
+		assert_eq!(total(&histories, |h| h.next(1)), Int::from(114));
 	}
 
 	#[test]
 	fn test_part2() {
-		assert_eq!(solve(INPUT).1, 2);
+		let histories = parse(INPUT).unwrap();
+		assert_eq!(total(&histories, |h| h.prev(1)), Int::from(2));
+	}
+
+	#[test]
+	fn test_horizon() {
+		let histories = parse(INPUT).unwrap();
+		let [line, triangle, cubic] = &histories[..] else { panic!() };
+
+		assert_eq!(line.next(3), Int::from(24));
+		assert_eq!(line.prev(3), Int::from(-9));
+		assert_eq!(triangle.at(1_000_000_000_000), Int::from((1_000_000_000_001 * 1_000_000_000_002) / 2));
+		assert_eq!(cubic.at(1_000_000_000_000).to_string(), "333333333332333333333337000000000010");
+		assert_eq!(cubic.prev(1_000_000_000_000).to_string(), "-333333333334333333333336999999999990");
+
+		// Only the last step is off the zero line, so it's `C(x, 20)`, way past i128.
+		let single = History::new(&[0; 20].into_iter().chain([1]).collect::<Vec<_>>());
+		assert_eq!(single.at(20), Int::from(1));
+		assert_eq!(single.at(1_000_000_000_000).to_string(), concat!(
+			"4110317622531204510117794286157532456314813743364110685116078746526494768214698618961889526098",
+			"7918209784280518226796851069286083127555627084472398403135218473510820402249556704035671081488",
+			"7270573257544374999999950000000000",
+		));
+		assert_eq!(single.at(-1), Int::from(1));
+	}
+
+	#[test]
+	fn test_degree() {
+		let histories = parse(INPUT).unwrap();
+		assert_eq!(histories.iter().map(History::degree).collect::<Vec<_>>(), [Some(1), Some(2), Some(3)]);
+		assert_eq!(histories.iter().map(|h| generate_polynomial(&h.polynomial())).collect::<Vec<_>>(), [
+			"3x",
+			"(1/2)x^2 + (3/2)x + 1",
+			"(1/3)x^3 - x^2 + (11/3)x + 10",
+		]);
+
+		let flat = History::new(&[0, 0, 0]);
+		assert_eq!(flat.degree(), Some(0));
+		assert_eq!(generate_polynomial(&flat.polynomial()), "0");
+
+		// Powers of two never settle, so it's only the degree 4 fit through them.
+		let powers = History::new(&[1, 2, 4, 8, 16]);
+		assert_eq!(powers.degree(), None);
+		assert_eq!(powers.next(1), Int::from(31));
+		assert_eq!(generate_polynomial(&powers.polynomial()), "(1/24)x^4 - (1/12)x^3 + (11/24)x^2 + (7/12)x + 1");
+		assert_eq!(History::new(&[5]).degree(), None);
+	}
+
+	#[test]
+	fn test_wide() {
+		// Alternating extremes double the differences at every row, way past `i128`.
+		let values = (0..70).map(|i| if i % 2 == 0 { i64::MAX } else { -i64::MAX }).collect::<Vec<_>>();
+		let line   = values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ");
+		let histories = parse(&line).unwrap();
+		let h = &histories[0];
+		assert_eq!(h.degree(), None);
+		assert_eq!(h.diffs.last().unwrap().to_i128(), None);
+		assert_eq!((0..70).map(|x| h.at(x)).collect::<Vec<_>>(), values.iter().map(|&v| Int::from(v as i128)).collect::<Vec<_>>());
+		assert_eq!(h.next(1).to_string(), "-10889035741470030829638172445062316687361");
 	}
 }
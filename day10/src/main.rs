@@ -7,42 +7,69 @@ fn main() -> Result<()> {
 	let mut input = String::new();
 	io::stdin().read_to_string(&mut input)?;
 
-	let (p1, p2) = solve(&input);
+	let (p1, p2) = solve(&input)?;
 	println!("p1 = {p1}");
 	println!("p2 = {p2}");
 
 	Ok(())
 }
 
-fn solve(input: &str) -> (i32, i32) {
+/// Finds `S` and the pipe under it from the neighbours connecting to it.
+fn start(m: &[u8], w: usize) -> Result<(usize, u8)> {
+	let s = m.iter().position(|&b| b == b'S').ok_or("There is no S")?;
+	let (row, col) = (s / (w + 1), s % (w + 1));
+
+	let at    = |p: usize| m.get(p).copied().unwrap_or(b'.');
+	let up    = row > 0     && matches!(at(s - w - 1), b'|' | b'F' | b'7');
+	let down  =                matches!(at(s + w + 1), b'|' | b'J' | b'L');
+	let left  = col > 0     && matches!(at(s - 1),     b'-' | b'L' | b'F');
+	let right = col + 1 < w && matches!(at(s + 1),     b'-' | b'J' | b'7');
+
+	let pipe = match (up, down, left, right) {
+		(true, true, false, false) => b'|',
+		(false, false, true, true) => b'-',
+		(true, false, false, true) => b'L',
+		(true, false, true, false) => b'J',
+		(false, true, true, false) => b'7',
+		(false, true, false, true) => b'F',
+		_ => {
+			let n = [up, down, left, right].iter().filter(|&&c| c).count();
+			return Err(format!("S at {row}:{col} needs 2 connecting pipes, got {n}").into());
+		},
+	};
+	Ok((s, pipe))
+}
+
+fn solve(input: &str) -> Result<(i32, i32)> {
 	let m = input.as_bytes();
-	let w = m.iter().position(|&b| b == b'\n').unwrap();
-	let s = m.iter().position(|&b| b == b'S').unwrap();
-
-	let (mut p, mut d) = if matches!(m[s - w - 1], b'|' | b'F' | b'7') {
-		(s - w - 1, 0)
-	} else if matches!(m[s + w + 1], b'|' | b'J' | b'L') {
-		(s + w + 1, 2)
-	} else {
-		(s - 1, 3)
+	let w = m.iter().position(|&b| b == b'\n').unwrap_or(m.len());
+
+	let (s, pipe) = start(m, w)?;
+	let (mut p, mut d) = match pipe {
+		b'|' | b'L' | b'J' => (s - w - 1, 0),
+		b'7' | b'F'        => (s + w + 1, 2),
+		_                  => (s + 1, 1),
 	};
 
+	// Stepping off a side lands on a newline or outside, which ends the
+	// walk just as anything else that doesn't continue the pipe.
 	let mut path = Vec::new();
 	loop {
 		path.push(p);
-		match (unsafe { m.get_unchecked(p) }, d) {
-			(b'|', 0) => p -= w + 1,
-			(b'|', 2) => p += w + 1,
-			(b'-', 3) => p -= 1,
-			(b'-', 1) => p += 1,
-			(b'L', 3) | (b'J', 1) => { p -= w + 1; d = 0; },
-			(b'7', 1) | (b'F', 3) => { p += w + 1; d = 2; },
-			(b'L', 2) | (b'F', 0) => { p += 1;     d = 1; },
-			(b'7', 0) | (b'J', 2) => { p -= 1;     d = 3; },
-			(b'S', _) => break,
-			_ => unreachable!(),
-		}
-	};
+		let next = match (m.get(p), d) {
+			(Some(b'|'), 0) => p.checked_sub(w + 1),
+			(Some(b'|'), 2) => Some(p + w + 1),
+			(Some(b'-'), 3) => p.checked_sub(1),
+			(Some(b'-'), 1) => Some(p + 1),
+			(Some(b'L'), 3) | (Some(b'J'), 1) => { d = 0; p.checked_sub(w + 1) },
+			(Some(b'7'), 1) | (Some(b'F'), 3) => { d = 2; Some(p + w + 1) },
+			(Some(b'L'), 2) | (Some(b'F'), 0) => { d = 1; Some(p + 1) },
+			(Some(b'7'), 0) | (Some(b'J'), 2) => { d = 3; p.checked_sub(1) },
+			(Some(b'S'), _) => break,
+			_ => None,
+		};
+		p = next.ok_or_else(|| format!("Loop from S breaks at {}:{}", p / (w + 1), p % (w + 1)))?;
+	}
 
 	let w   = w as i32 + 1;
 	let pnt = |p: usize, w: i32| {
@@ -60,7 +87,7 @@ fn solve(input: &str) -> (i32, i32) {
 
 	let area = area.abs() / 2 + 1 - path.len() as i32 / 2;
 
-	(path.len() as i32 / 2, area)
+	Ok((path.len() as i32 / 2, area))
 }
 
 #[cfg(test)]
@@ -102,13 +129,42 @@ L--J.L7...LJS7F-7L7.
 
 	#[test]
 	fn test_part1() {
-		assert_eq!(solve(INPUT1).0, 4);
-		assert_eq!(solve(INPUT2).0, 8);
+		assert_eq!(solve(INPUT1).unwrap().0, 4);
+		assert_eq!(solve(INPUT2).unwrap().0, 8);
 	}
 
 	#[test]
 	fn test_part2() {
-		assert_eq!(solve(INPUT3).1, 4);
-		assert_eq!(solve(INPUT4).1, 8);
+		assert_eq!(solve(INPUT3).unwrap().1, 4);
+		assert_eq!(solve(INPUT4).unwrap().1, 8);
+	}
+
+	#[test]
+	fn test_start() {
+		let pipe = |input: &str| start(input.as_bytes(), input.find('\n').unwrap()).map(|(_, p)| p as char);
+		assert_eq!(pipe(INPUT1).unwrap(), 'F');
+		assert_eq!(pipe(INPUT2).unwrap(), 'F');
+		assert_eq!(pipe(INPUT4).unwrap(), 'F');
+
+		// Corners, where `S` has nothing on two sides.
+		assert_eq!(solve("S7\nLJ").unwrap(), (2, 0));
+		assert_eq!(solve("F7\nLS").unwrap(), (2, 0));
+		assert_eq!(solve("F-S\nL-J\n").unwrap(), (3, 0));
+
+		assert_eq!(pipe("-S.\n...").unwrap_err().to_string(), "S at 0:1 needs 2 connecting pipes, got 1");
+		assert_eq!(pipe(".|.\n-S-\n.|.").unwrap_err().to_string(), "S at 1:1 needs 2 connecting pipes, got 4");
+		assert_eq!(pipe("S7\n.|").unwrap_err().to_string(), "S at 0:0 needs 2 connecting pipes, got 1");
+		assert!(pipe("..\n..").is_err());
+	}
+
+	#[test]
+	fn test_broken_loop() {
+		let error = |input: &str| solve(input).unwrap_err().to_string();
+		assert_eq!(error("-S-\n...\n"), "Loop from S breaks at 0:3");
+		assert_eq!(error("F-S\n|.|\n"), "Loop from S breaks at 2:2");
+		assert_eq!(error("F-S\n|.|"), "Loop from S breaks at 2:2");
+		assert_eq!(error("S7\nL-"), "Loop from S breaks at 1:2");
+		assert_eq!(error("-S7\n..J"), "Loop from S breaks at 1:1");
+		assert_eq!(error(".|.\nFS.\n..."), "Loop from S breaks at 0:1");
 	}
 }